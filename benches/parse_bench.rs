@@ -0,0 +1,170 @@
+//! Simple timing benchmarks, run with `cargo bench`.
+
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::hint::black_box;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use wc_parser::datetime::days_before_months;
+use wc_parser::models::ParseStringOptions;
+use wc_parser::parser::{aggregate_lines, make_array_of_messages, make_array_of_messages_with_errors, parse_messages, Lines};
+use wc_parser::{parse_file, parse_string, parse_string_count};
+
+/// The small export used by the integration tests.
+const CHAT_EXAMPLE: &str = r#"06/03/2017, 00:45 - Messages to this group are now secured with end-to-end encryption. Tap for more info.
+06/03/2017, 00:45 - You created group "ShortChat"
+06/03/2017, 00:45 - Sample User: This is a test message
+08/05/2017, 01:48 - TestBot: Hey I'm a test too!
+09/04/2017, 01:50 - +410123456789: How are you?
+Is everything alright?"#;
+
+/// The system allocator, counting the allocations made.
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.alloc(layout) }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        unsafe { System.dealloc(ptr, layout) }
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        unsafe { System.realloc(ptr, layout, new_size) }
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Runs `f` once and prints the number of allocations and reallocations it
+/// made.
+fn count_allocations<R>(name: &str, f: impl FnOnce() -> R) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(f());
+    let allocations = ALLOCATIONS.load(Ordering::Relaxed) - before;
+    println!("{:<40} {:>12} allocations", name, allocations);
+    allocations
+}
+
+/// Runs `f` repeatedly for about a second and prints the average time per run.
+fn bench<R>(name: &str, mut f: impl FnMut() -> R) -> Duration {
+    // Warm up caches and lazily compiled regexes
+    black_box(f());
+
+    let start = Instant::now();
+    let mut iterations = 0u32;
+    while start.elapsed() < Duration::from_secs(1) {
+        black_box(f());
+        iterations += 1;
+    }
+
+    let average = start.elapsed() / iterations;
+    println!("{:<40} {:>12.3?} / iter ({} iterations)", name, average, iterations);
+    average
+}
+
+/// Builds a synthetic export with `lines` lines, mixing user, system and
+/// multiline messages.
+fn synthetic_export(lines: usize) -> String {
+    let mut export = String::new();
+    for i in 0..lines {
+        let day = i % 28 + 1;
+        let month = i / 28 % 12 + 1;
+        let (hour, minute) = (i % 24, i % 60);
+        match i % 10 {
+            0 => export.push_str(&format!("{}/{}/2020, {:02}:{:02} - Alice added Bob\n", day, month, hour, minute)),
+            1 => export.push_str("and this is the second line of the previous message\n"),
+            _ => export.push_str(&format!(
+                "{}/{}/2020, {:02}:{:02} - User {}: message number {}\n",
+                day,
+                month,
+                hour,
+                minute,
+                i % 7,
+                i
+            )),
+        }
+    }
+    export
+}
+
+fn main() {
+    bench("parse_string (CHAT_EXAMPLE)", || parse_string(black_box(CHAT_EXAMPLE), None).unwrap());
+
+    // 1000 lines make 900 messages, the rest are continuation lines
+    let small_export = synthetic_export(1_000);
+    bench("parse_string (1 000 lines)", || parse_string(black_box(&small_export), None).unwrap());
+    bench("make_array_of_messages (1 000 lines)", || make_array_of_messages(Lines::new(black_box(&small_export))));
+    let raw = make_array_of_messages(Lines::new(&small_export));
+    bench("parse_messages (1 000 lines)", || parse_messages(black_box(&raw), &ParseStringOptions::default()).unwrap());
+
+    let numeric_dates: Vec<Vec<i32>> = (0..1000).map(|i| vec![i % 12 + 1, i % 12 + 1, 2020]).collect();
+    bench("days_before_months (1 000 dates)", || days_before_months(black_box(&numeric_dates)));
+
+    let export = synthetic_export(10_000);
+
+    // Lines collected up front tell the aggregation how many messages to
+    // expect, streamed lines don't
+    let lines: Vec<&str> = Lines::new(&export).collect();
+    let presized = count_allocations("aggregate_lines, Vec (10 000 lines)", || make_array_of_messages_with_errors(black_box(&lines), false));
+    let growing = count_allocations("aggregate_lines, Lines (10 000 lines)", || aggregate_lines(Lines::new(black_box(&export)), false));
+    println!("allocations saved by presizing: {}", growing - presized);
+
+    let full = bench("parse_string (10 000 lines)", || parse_string(black_box(&export), None).unwrap());
+    let count = bench("parse_string_count (10 000 lines)", || parse_string_count(black_box(&export)));
+    println!("parse_string_count speedup: {:.1}x", full.as_secs_f64() / count.as_secs_f64());
+
+    // Compares the memory-mapped `parse_file` with reading the file first
+    let path = std::env::temp_dir().join("wc_parser_bench.txt");
+    std::fs::write(&path, &export).unwrap();
+    let mapped = bench("parse_file (10 000 lines)", || parse_file(black_box(&path), None).unwrap());
+    let read = bench("read_to_string + parse_string", || {
+        let text = std::fs::read_to_string(black_box(&path)).unwrap();
+        parse_string(&text, None).unwrap()
+    });
+    println!("parse_file speedup: {:.2}x", read.as_secs_f64() / mapped.as_secs_f64());
+    std::fs::remove_file(&path).unwrap();
+
+    let large_export = synthetic_export(100_000);
+    let collected = bench("split + collect (100 000 lines)", || {
+        let lines: Vec<&str> = black_box(&large_export).split('\n').map(|l| l.trim_end_matches('\r')).collect();
+        make_array_of_messages_with_errors(&lines, false)
+    });
+    let streamed = bench("Lines (100 000 lines)", || aggregate_lines(Lines::new(black_box(&large_export)), false));
+    println!("Lines speedup: {:.2}x", collected.as_secs_f64() / streamed.as_secs_f64());
+
+    // Where parsing in parallel starts paying off, see `PARALLEL_THRESHOLD`
+    #[cfg(feature = "parallel")]
+    for messages in [10, 25, 50, 100, 500] {
+        let raw = make_array_of_messages(Lines::new(&export).take(messages));
+        let time = |threshold| {
+            let options = ParseStringOptions {
+                parallel_threshold: Some(threshold),
+                ..Default::default()
+            };
+            bench(&format!("parse_messages ({} messages, {})", messages, if threshold == 0 { "parallel" } else { "sequential" }), || {
+                parse_messages(black_box(&raw), &options).unwrap()
+            })
+        };
+        let sequential = time(usize::MAX);
+        let parallel = time(0);
+        println!("parallel speedup: {:.2}x", sequential.as_secs_f64() / parallel.as_secs_f64());
+    }
+
+    #[cfg(feature = "parallel")]
+    for threads in [1, 2, 4] {
+        let pool = ParseStringOptions::with_thread_count(threads).thread_pool;
+        bench(&format!("parse_string ({} threads)", threads), || {
+            let options = ParseStringOptions {
+                thread_pool: pool.clone(),
+                ..Default::default()
+            };
+            parse_string(black_box(&export), Some(options)).unwrap()
+        });
+    }
+}
@@ -17,7 +17,7 @@ fn main() {
     }
 
     let mut sorted_users: Vec<_> = user_counts.into_iter().collect();
-    sorted_users.sort_by(|a, b| b.1.cmp(&a.1));
+    sorted_users.sort_by_key(|b| std::cmp::Reverse(b.1));
 
     println!("Users by message count:");
     for (user, count) in sorted_users {
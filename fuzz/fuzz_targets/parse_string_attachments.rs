@@ -0,0 +1,12 @@
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use wc_parser::models::ParseStringOptions;
+
+fuzz_target!(|data: &str| {
+    let options = ParseStringOptions {
+        parse_attachments: true,
+        ..Default::default()
+    };
+    let _ = wc_parser::parse_string(data, Some(options));
+});
@@ -0,0 +1,1320 @@
+use crate::models::Message;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeZone, Timelike, Utc, Weekday};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet, BinaryHeap, HashMap, HashSet};
+use std::ops::Range;
+
+/// The size of the buckets used by [`group_messages_by_date`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DateGranularity {
+    /// One bucket per calendar day.
+    Day,
+    /// One bucket per ISO week, keyed on its Monday.
+    Week,
+    /// One bucket per month, keyed on its first day.
+    Month,
+    /// One bucket per year, keyed on January 1.
+    Year,
+}
+
+/// Returns the first day of the bucket `date` falls in for the given
+/// granularity.
+fn bucket_start(date: NaiveDate, granularity: DateGranularity) -> NaiveDate {
+    match granularity {
+        DateGranularity::Day => date,
+        DateGranularity::Week => {
+            date - Duration::days(date.weekday().num_days_from_monday() as i64)
+        }
+        DateGranularity::Month => date.with_day(1).unwrap(),
+        DateGranularity::Year => NaiveDate::from_ymd_opt(date.year(), 1, 1).unwrap(),
+    }
+}
+
+/// Groups messages by the (UTC) date they were sent on, using buckets of the
+/// size specified by `granularity`.
+///
+/// Keys are the first day of each bucket, so the map iterates in chronological
+/// order. Messages keep their original relative order inside each bucket.
+pub fn group_messages_by_date(
+    messages: &[Message],
+    granularity: DateGranularity,
+) -> BTreeMap<NaiveDate, Vec<&Message>> {
+    let mut groups: BTreeMap<NaiveDate, Vec<&Message>> = BTreeMap::new();
+
+    for message in messages {
+        let key = bucket_start(message.date.date_naive(), granularity);
+        groups.entry(key).or_default().push(message);
+    }
+
+    groups
+}
+
+/// Counts the words of all the user messages, see [`Message::word_count`].
+/// System messages are skipped.
+pub fn total_word_count(messages: &[Message]) -> usize {
+    messages.iter().filter(|m| m.is_user()).map(Message::word_count).sum()
+}
+
+/// Finds the breaks in a conversation: every pair of consecutive messages
+/// separated by strictly more than `min_gap`.
+///
+/// Output is a list of `(before_index, after_index, actual_gap)` tuples, where
+/// the indexes point into `messages`.
+pub fn find_gaps(messages: &[Message], min_gap: Duration) -> Vec<(usize, usize, Duration)> {
+    messages
+        .windows(2)
+        .enumerate()
+        .map(|(idx, w)| (idx, idx + 1, w[1].date - w[0].date))
+        .filter(|(_, _, gap)| *gap > min_gap)
+        .collect()
+}
+
+/// Finds the largest break between two consecutive messages.
+///
+/// Output is `None` if there are fewer than two messages. If several gaps
+/// have the same length, the earliest one is returned.
+pub fn largest_gap(messages: &[Message]) -> Option<(usize, usize, Duration)> {
+    messages
+        .windows(2)
+        .enumerate()
+        .map(|(idx, w)| (idx, idx + 1, w[1].date - w[0].date))
+        .fold(None, |largest, current| match largest {
+            Some(largest @ (_, _, gap)) if gap >= current.2 => Some(largest),
+            _ => Some(current),
+        })
+}
+
+/// Computes the time it took to reply to a message: the elapsed time between
+/// every pair of consecutive messages sent by different authors.
+///
+/// System messages are skipped, so they never break or start a pair.
+pub fn compute_response_times(messages: &[Message]) -> Vec<Duration> {
+    let user_messages: Vec<&Message> = messages.iter().filter(|m| m.is_user()).collect();
+
+    user_messages
+        .windows(2)
+        .filter(|w| w[0].author != w[1].author)
+        .map(|w| w[1].date - w[0].date)
+        .collect()
+}
+
+/// Computes the arithmetic mean of the response times in a conversation, see
+/// [`compute_response_times`].
+///
+/// Output is `None` if there are fewer than two replies.
+pub fn average_response_time(messages: &[Message]) -> Option<Duration> {
+    let response_times = compute_response_times(messages);
+    if response_times.len() < 2 {
+        return None;
+    }
+
+    let total = response_times.iter().fold(Duration::zero(), |acc, d| acc + *d);
+    Some(total / response_times.len() as i32)
+}
+
+/// Computes the median of the response times in a conversation, see
+/// [`compute_response_times`].
+///
+/// With an even number of replies the lower of the two middle values is used,
+/// so the median is always one of the actual response times. Output is `None`
+/// if there are fewer than two replies.
+pub fn median_response_time(messages: &[Message]) -> Option<Duration> {
+    let mut response_times = compute_response_times(messages);
+    if response_times.len() < 2 {
+        return None;
+    }
+    response_times.sort();
+    median_of_sorted(&response_times)
+}
+
+/// Returns the median of sorted durations, the lower of the two middle values
+/// if there is an even number of them.
+fn median_of_sorted(sorted: &[Duration]) -> Option<Duration> {
+    if sorted.is_empty() {
+        return None;
+    }
+    Some(sorted[(sorted.len() - 1) / 2])
+}
+
+/// Returns the `p`-th percentile (between `0` and `100`) of non-empty sorted
+/// durations, using the nearest-rank method.
+fn percentile_of_sorted(sorted: &[Duration], p: usize) -> Duration {
+    let rank = (p * sorted.len()).div_ceil(100).max(1);
+    sorted[rank - 1]
+}
+
+/// Statistics about the time one participant takes to reply to another.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ResponseTimeStats {
+    /// The number of replies.
+    pub count: usize,
+    /// The median response time, the lower of the two middle values if there
+    /// is an even number of replies.
+    pub median: Duration,
+    /// The 90th percentile of the response times (nearest-rank method).
+    pub p90: Duration,
+}
+
+/// Computes response time statistics for every pair of participants.
+///
+/// A reply is the next user message, when sent by another author than the
+/// previous user message; the same rule as [`compute_response_times`]. Keys are
+/// `(author, replier)`: `stats[("Luke", "Leia")]` describes how long Leia
+/// takes to reply to Luke. Pairs without replies are not in the map.
+pub fn response_time_stats_by_pair(messages: &[Message]) -> BTreeMap<(String, String), ResponseTimeStats> {
+    let user_messages: Vec<&Message> = messages.iter().filter(|m| m.is_user()).collect();
+    let mut response_times: BTreeMap<(String, String), Vec<Duration>> = BTreeMap::new();
+
+    for w in user_messages.windows(2) {
+        if let (Some(author), Some(replier)) = (&w[0].author, &w[1].author)
+            && author != replier
+        {
+            response_times
+                .entry((author.clone(), replier.clone()))
+                .or_default()
+                .push(w[1].date - w[0].date);
+        }
+    }
+
+    response_times
+        .into_iter()
+        .map(|(pair, mut times)| {
+            times.sort();
+            let stats = ResponseTimeStats {
+                count: times.len(),
+                median: median_of_sorted(&times).unwrap(),
+                p90: percentile_of_sorted(&times, 90),
+            };
+            (pair, stats)
+        })
+        .collect()
+}
+
+/// Returns the `n` messages with the smallest keys, in increasing key order.
+/// Messages with equal keys keep their relative order.
+///
+/// Only `n` messages are kept in the heap at any time.
+fn smallest_by_key<K: Ord>(messages: &[Message], n: usize, key: impl Fn(&Message) -> K) -> Vec<&Message> {
+    if n == 0 {
+        return Vec::new();
+    }
+
+    let mut heap = BinaryHeap::with_capacity(n + 1);
+    for (idx, message) in messages.iter().enumerate() {
+        heap.push((key(message), idx));
+        if heap.len() > n {
+            heap.pop();
+        }
+    }
+    heap.into_sorted_vec().into_iter().map(|(_, idx)| &messages[idx]).collect()
+}
+
+/// Finds the `n` longest messages, by length in bytes, longest first. System
+/// messages are included.
+///
+/// Messages with the same length keep their relative order. All the messages
+/// are returned if there are fewer than `n`.
+pub fn find_longest_messages(messages: &[Message], n: usize) -> Vec<&Message> {
+    smallest_by_key(messages, n, |m| Reverse(m.message.len()))
+}
+
+/// Finds the `n` shortest messages, by length in bytes, shortest first. System
+/// messages are included.
+///
+/// Messages with the same length keep their relative order. All the messages
+/// are returned if there are fewer than `n`.
+pub fn find_shortest_messages(messages: &[Message], n: usize) -> Vec<&Message> {
+    smallest_by_key(messages, n, |m| m.message.len())
+}
+
+/// Counts the user messages sent on each day, in the given timezone. System
+/// messages are skipped.
+pub fn messages_per_day<Tz: TimeZone>(messages: &[Message], tz: &Tz) -> BTreeMap<NaiveDate, usize> {
+    let mut counts: BTreeMap<NaiveDate, usize> = BTreeMap::new();
+    for message in messages.iter().filter(|m| m.is_user()) {
+        *counts.entry(message.date.with_timezone(tz).date_naive()).or_default() += 1;
+    }
+    counts
+}
+
+/// Counts the user messages sent during each hour of the day, in the given
+/// timezone. Index `i` holds the count for hour `i`. System messages are
+/// skipped.
+pub fn messages_per_hour<Tz: TimeZone>(messages: &[Message], tz: &Tz) -> [usize; 24] {
+    let mut counts = [0; 24];
+    for message in messages.iter().filter(|m| m.is_user()) {
+        counts[message.date.with_timezone(tz).hour() as usize] += 1;
+    }
+    counts
+}
+
+/// Finds the day with the most user messages, in the given timezone, and its
+/// message count.
+///
+/// Ties are broken by the earliest day. Output is `None` if there are no user
+/// messages.
+pub fn busiest_day<Tz: TimeZone>(messages: &[Message], tz: &Tz) -> Option<(NaiveDate, usize)> {
+    messages_per_day(messages, tz)
+        .into_iter()
+        .fold(None, |busiest, (day, count)| match busiest {
+            Some((_, max)) if max >= count => busiest,
+            _ => Some((day, count)),
+        })
+}
+
+/// Finds the hour of the day with the most user messages, in the given
+/// timezone, and its message count.
+///
+/// Ties are broken by the earliest hour. Output is `None` if there are no user
+/// messages.
+pub fn busiest_hour<Tz: TimeZone>(messages: &[Message], tz: &Tz) -> Option<(u32, usize)> {
+    messages_per_hour(messages, tz)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, count)| *count > 0)
+        .fold(None, |busiest, (hour, count)| match busiest {
+            Some((_, max)) if max >= count => busiest,
+            _ => Some((hour as u32, count)),
+        })
+}
+
+/// Counts the user messages sent during each hour of the day, in UTC. Same as
+/// [`messages_per_hour`] with `&Utc`.
+pub fn hourly_message_counts(messages: &[Message]) -> [usize; 24] {
+    messages_per_hour(messages, &Utc)
+}
+
+/// Finds the hour of the day, in UTC, with the most user messages.
+///
+/// Ties are broken by the earliest hour. Output is `None` if there are no user
+/// messages. See [`busiest_hour`] for other timezones.
+pub fn peak_activity_hour(messages: &[Message]) -> Option<u32> {
+    busiest_hour(messages, &Utc).map(|(hour, _)| hour)
+}
+
+/// Counts the user messages sent on each day of the week, in UTC. Index `0`
+/// holds the count for Monday and index `6` the one for Sunday. System
+/// messages are skipped.
+pub fn weekday_message_counts(messages: &[Message]) -> [usize; 7] {
+    let mut counts = [0; 7];
+    for message in messages.iter().filter(|m| m.is_user()) {
+        counts[message.date.weekday().num_days_from_monday() as usize] += 1;
+    }
+    counts
+}
+
+/// Finds the day of the week, in UTC, with the most user messages.
+///
+/// Ties are broken by the earliest day, starting on Monday. Output is `None`
+/// if there are no user messages.
+pub fn most_active_weekday(messages: &[Message]) -> Option<Weekday> {
+    weekday_message_counts(messages)
+        .into_iter()
+        .enumerate()
+        .filter(|(_, count)| *count > 0)
+        .fold(None, |busiest, (day, count)| match busiest {
+            Some((_, max)) if max >= count => busiest,
+            _ => Some((day, count)),
+        })
+        .map(|(day, _)| Weekday::try_from(day as u8).unwrap())
+}
+
+/// Returns the (UTC) days with at least one user message, in chronological
+/// order.
+fn active_days(messages: &[Message]) -> BTreeSet<NaiveDate> {
+    messages.iter().filter(|m| m.is_user()).map(|m| m.date.date_naive()).collect()
+}
+
+/// Finds the longest streak of consecutive days, in UTC, with at least one
+/// user message. System messages are skipped.
+///
+/// Output is `0` if there are no user messages.
+pub fn consecutive_active_days(messages: &[Message]) -> usize {
+    let mut longest = 0;
+    let mut streak = 0;
+    let mut previous: Option<NaiveDate> = None;
+
+    for day in active_days(messages) {
+        streak = match previous {
+            Some(previous) if day - previous == Duration::days(1) => streak + 1,
+            _ => 1,
+        };
+        longest = longest.max(streak);
+        previous = Some(day);
+    }
+    longest
+}
+
+/// Counts the consecutive days, in UTC, with at least one user message up to
+/// `today`. System messages and messages after `today` are skipped.
+///
+/// A streak is still current when the last message was sent yesterday, as
+/// today's message may just not have been sent yet. Output is `0` if the last
+/// message is older.
+pub fn current_streak(messages: &[Message], today: NaiveDate) -> usize {
+    let days = active_days(messages);
+    let mut days = days.range(..=today).rev();
+    let Some(mut previous) = days.next().copied() else {
+        return 0;
+    };
+    if today - previous > Duration::days(1) {
+        return 0;
+    }
+
+    let mut streak = 1;
+    for &day in days {
+        if previous - day != Duration::days(1) {
+            break;
+        }
+        streak += 1;
+        previous = day;
+    }
+    streak
+}
+
+/// Tags every message with the index of the conversation session it belongs
+/// to, storing it in `Message::session_id`.
+///
+/// A new session starts whenever two consecutive messages are separated by
+/// strictly more than `session_gap`. Sessions are numbered from `0`.
+pub fn assign_session_ids(messages: &mut [Message], session_gap: Duration) {
+    let mut session_id = 0;
+    let mut previous_date = None;
+
+    for message in messages.iter_mut() {
+        if previous_date.is_some_and(|previous| message.date - previous > session_gap) {
+            session_id += 1;
+        }
+        message.session_id = Some(session_id);
+        previous_date = Some(message.date);
+    }
+}
+
+/// Splits a conversation into sessions, returned as ranges of indexes into
+/// `messages`.
+///
+/// Sessions are split the same way as in [`assign_session_ids`]: whenever two
+/// consecutive messages are separated by strictly more than `session_gap`.
+/// Output is empty if there are no messages.
+pub fn split_into_sessions(messages: &[Message], session_gap: Duration) -> Vec<Range<usize>> {
+    let mut sessions = Vec::new();
+    let mut start = 0;
+
+    for (idx, w) in messages.windows(2).enumerate() {
+        if w[1].date - w[0].date > session_gap {
+            sessions.push(start..idx + 1);
+            start = idx + 1;
+        }
+    }
+    if !messages.is_empty() {
+        sessions.push(start..messages.len());
+    }
+    sessions
+}
+
+/// Same as [`split_into_sessions`], returning the messages of each session.
+pub fn session_slices(messages: &[Message], session_gap: Duration) -> Vec<&[Message]> {
+    split_into_sessions(messages, session_gap)
+        .into_iter()
+        .map(|range| &messages[range])
+        .collect()
+}
+
+/// A slice of a conversation covering a span of time, see
+/// [`sliding_windows`].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct MessageWindow<'a> {
+    /// The messages sent between `start` (included) and `end` (excluded).
+    pub messages: &'a [Message],
+    /// The start of the window.
+    pub start: DateTime<Utc>,
+    /// The end of the window, excluded.
+    pub end: DateTime<Utc>,
+    /// The index of the window, starting from `0`.
+    pub window_index: usize,
+}
+
+/// Splits a conversation into windows of `window_size`, the first one starting
+/// with the first message and every next one `step` later.
+///
+/// Windows overlap when `step` is smaller than `window_size`, and may be empty
+/// when it is larger. The last window is the last one starting at or before
+/// the last message. The messages must be sorted by date. Output is empty if
+/// there are no messages.
+///
+/// Panics if `step` is not positive.
+pub fn sliding_windows(messages: &[Message], window_size: Duration, step: Duration) -> impl Iterator<Item = MessageWindow<'_>> {
+    assert!(step > Duration::zero(), "the step between windows must be positive");
+    let span = match (messages.first(), messages.last()) {
+        (Some(first), Some(last)) => Some((first.date, last.date)),
+        _ => None,
+    };
+
+    (0..)
+        .map_while(move |window_index: usize| {
+            let (first, last) = span?;
+            let start = first + step * i32::try_from(window_index).ok()?;
+            if start > last {
+                return None;
+            }
+            let end = start + window_size;
+            let from = messages.partition_point(|m| m.date < start);
+            let to = messages.partition_point(|m| m.date < end).max(from);
+            Some(MessageWindow {
+                messages: &messages[from..to],
+                start,
+                end,
+                window_index,
+            })
+        })
+}
+
+/// Computes the time spent chatting: the summed length of all the sessions,
+/// from the first to the last message of each.
+///
+/// Sessions are split the same way as in [`assign_session_ids`]: whenever two
+/// consecutive messages are separated by strictly more than `session_gap`.
+/// Sessions with a single message last zero seconds.
+pub fn total_active_duration(messages: &[Message], session_gap: Duration) -> Duration {
+    messages
+        .windows(2)
+        .map(|w| w[1].date - w[0].date)
+        .filter(|gap| *gap <= session_gap)
+        .fold(Duration::zero(), |total, gap| total + gap)
+}
+
+/// Keeps the messages sent by one of `authors`, in their original order.
+/// System messages are never kept.
+///
+/// When `case_sensitive` is `false`, names are compared in lowercase, so
+/// `"alice"` matches messages sent by `"Alice"`.
+pub fn filter_by_author<'a>(messages: &'a [Message], authors: &[&str], case_sensitive: bool) -> Vec<&'a Message> {
+    if case_sensitive {
+        return messages
+            .iter()
+            .filter(|m| m.author.as_deref().is_some_and(|author| authors.contains(&author)))
+            .collect();
+    }
+
+    let authors: HashSet<String> = authors.iter().map(|a| a.to_lowercase()).collect();
+    messages
+        .iter()
+        .filter(|m| m.author.as_ref().is_some_and(|author| authors.contains(&author.to_lowercase())))
+        .collect()
+}
+
+/// Leaves out the messages sent by one of `authors`, keeping the others and
+/// the system messages in their original order. Names are case sensitive.
+pub fn exclude_authors<'a>(messages: &'a [Message], authors: &[&str]) -> Vec<&'a Message> {
+    messages
+        .iter()
+        .filter(|m| !m.author.as_deref().is_some_and(|author| authors.contains(&author)))
+        .collect()
+}
+
+/// Returns the messages sent between `start` and `end`, both included, as a
+/// slice of `messages` found with a binary search.
+///
+/// The messages must be sorted by date, see [`sort_messages`]; use
+/// [`filter_by_date_range_vec`] otherwise. Output is empty if `start` is after
+/// `end`.
+pub fn filter_by_date_range(messages: &[Message], start: DateTime<Utc>, end: DateTime<Utc>) -> &[Message] {
+    let from = messages.partition_point(|m| m.date < start);
+    let to = messages.partition_point(|m| m.date <= end).max(from);
+    &messages[from..to]
+}
+
+/// Same as [`filter_by_date_range`] for messages that may not be sorted, with
+/// a scan of every message. The messages keep their original order.
+pub fn filter_by_date_range_vec(messages: &[Message], start: DateTime<Utc>, end: DateTime<Utc>) -> Vec<&Message> {
+    messages.iter().filter(|m| start <= m.date && m.date <= end).collect()
+}
+
+/// Marks the messages whose `(date, author, message)` was already seen earlier
+/// in `messages`.
+fn duplicate_flags(messages: &[Message]) -> Vec<bool> {
+    let mut seen: HashSet<(DateTime<Utc>, Option<&str>, &str)> = HashSet::with_capacity(messages.len());
+    messages
+        .iter()
+        .map(|m| !seen.insert((m.date, m.author.as_deref(), m.message.as_str())))
+        .collect()
+}
+
+/// Removes duplicated messages, such as the ones found after combining two
+/// exports of the same chat.
+///
+/// Messages are duplicates when they have the same date, author and text, the
+/// first occurrence is kept. They don't need to be next to each other.
+pub fn dedup_messages(messages: &mut Vec<Message>) {
+    let mut duplicates = duplicate_flags(messages).into_iter();
+    messages.retain(|_| !duplicates.next().unwrap());
+}
+
+/// Same as [`dedup_messages`], taking and returning the messages by value. The
+/// messages that are kept stay in their original order.
+pub fn dedup_messages_stable(messages: Vec<Message>) -> Vec<Message> {
+    let duplicates = duplicate_flags(&messages);
+    messages
+        .into_iter()
+        .zip(duplicates)
+        .filter_map(|(message, duplicate)| (!duplicate).then_some(message))
+        .collect()
+}
+
+/// Sorts messages by date, oldest first. Messages sent at the same time keep
+/// their relative order.
+///
+/// Useful after merging exports, or near backup boundaries where clock skew
+/// leaves a few messages out of order.
+pub fn sort_messages(messages: &mut [Message]) {
+    messages.sort_by_key(|m| m.date);
+}
+
+/// Sorts messages by date, newest first. Messages sent at the same time keep
+/// their relative order.
+pub fn sort_messages_desc(messages: &mut [Message]) {
+    messages.sort_by_key(|m| Reverse(m.date));
+}
+
+/// Overall statistics about a chat, see [`MessageStats::compute`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct MessageStats {
+    /// The number of messages, user and system ones.
+    pub total_messages: usize,
+    /// The number of messages sent by a participant.
+    pub user_messages: usize,
+    /// The number of system messages.
+    pub system_messages: usize,
+    /// The number of user messages sharing a media file, see
+    /// [`Message::is_media`].
+    pub media_messages: usize,
+    /// The number of characters of all the user messages.
+    pub total_characters: usize,
+    /// The number of words of all the user messages, see
+    /// [`total_word_count`].
+    pub total_words: usize,
+    /// The number of participants who sent at least one message.
+    pub unique_authors: usize,
+    /// The dates of the earliest and latest messages, `None` if there are no
+    /// messages.
+    pub date_range: Option<(DateTime<Utc>, DateTime<Utc>)>,
+    /// The number of messages sent by each participant.
+    pub messages_per_author: HashMap<String, usize>,
+}
+
+impl MessageStats {
+    /// Computes the statistics of `messages` in a single pass. The messages
+    /// don't need to be sorted.
+    pub fn compute(messages: &[Message]) -> MessageStats {
+        let mut stats = MessageStats::default();
+
+        for message in messages {
+            stats.total_messages += 1;
+            stats.date_range = match stats.date_range {
+                Some((first, last)) => Some((first.min(message.date), last.max(message.date))),
+                None => Some((message.date, message.date)),
+            };
+
+            let Some(author) = &message.author else {
+                stats.system_messages += 1;
+                continue;
+            };
+            stats.user_messages += 1;
+            if message.is_media() {
+                stats.media_messages += 1;
+            }
+            stats.total_characters += message.message.chars().count();
+            stats.total_words += message.word_count();
+            *stats.messages_per_author.entry(author.clone()).or_default() += 1;
+        }
+
+        stats.unique_authors = stats.messages_per_author.len();
+        stats
+    }
+
+    /// Returns the average number of characters of the user messages, `0.0` if
+    /// there are none.
+    pub fn average_message_length(&self) -> f64 {
+        if self.user_messages == 0 {
+            return 0.0;
+        }
+        self.total_characters as f64 / self.user_messages as f64
+    }
+
+    /// Returns the participant who sent the most messages.
+    ///
+    /// Ties are broken by the alphabetically first name. Output is `None` if
+    /// there are no user messages.
+    pub fn most_active_author(&self) -> Option<&str> {
+        self.messages_per_author
+            .iter()
+            .max_by(|(a, a_count), (b, b_count)| a_count.cmp(b_count).then_with(|| b.cmp(a)))
+            .map(|(author, _)| author.as_str())
+    }
+}
+
+/// Statistics about the messages of one participant, see
+/// [`compute_author_stats`].
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct AuthorStats {
+    /// The name of the participant.
+    pub name: String,
+    /// The number of messages they sent.
+    pub message_count: usize,
+    /// The number of words of their messages, see [`Message::word_count`].
+    pub word_count: usize,
+    /// The number of characters of their messages.
+    pub char_count: usize,
+    /// The number of their messages sharing a media file, see
+    /// [`Message::is_media`].
+    pub media_count: usize,
+    /// The date of their earliest message.
+    pub first_message: Option<DateTime<Utc>>,
+    /// The date of their latest message.
+    pub last_message: Option<DateTime<Utc>>,
+    /// The average number of characters of their messages.
+    pub average_message_length: f64,
+}
+
+impl AuthorStats {
+    /// Returns the share of `total_messages` sent by the participant, between
+    /// `0.0` and `1.0`. Output is `0.0` if `total_messages` is zero.
+    pub fn activity_ratio(&self, total_messages: usize) -> f64 {
+        if total_messages == 0 {
+            return 0.0;
+        }
+        self.message_count as f64 / total_messages as f64
+    }
+}
+
+/// Computes statistics about every participant, keyed by name. System messages
+/// are skipped. The messages don't need to be sorted.
+pub fn compute_author_stats(messages: &[Message]) -> HashMap<String, AuthorStats> {
+    let mut stats: HashMap<String, AuthorStats> = HashMap::new();
+
+    for message in messages {
+        let Some(author) = &message.author else {
+            continue;
+        };
+        let author_stats = stats.entry(author.clone()).or_insert_with(|| AuthorStats {
+            name: author.clone(),
+            ..Default::default()
+        });
+        author_stats.message_count += 1;
+        author_stats.word_count += message.word_count();
+        author_stats.char_count += message.message.chars().count();
+        if message.is_media() {
+            author_stats.media_count += 1;
+        }
+        author_stats.first_message = Some(author_stats.first_message.map_or(message.date, |d| d.min(message.date)));
+        author_stats.last_message = Some(author_stats.last_message.map_or(message.date, |d| d.max(message.date)));
+    }
+
+    for author_stats in stats.values_mut() {
+        author_stats.average_message_length = author_stats.char_count as f64 / author_stats.message_count as f64;
+    }
+    stats
+}
+
+/// How often messages are sent in a chat, see [`MessageFrequency::compute`].
+///
+/// The rates are averaged over the periods with at least one message, so long
+/// silences don't lower them. Dates are in UTC and system messages are
+/// skipped.
+#[derive(Debug, Clone, PartialEq)]
+pub struct MessageFrequency {
+    /// The average number of messages on the days with at least one message.
+    pub messages_per_day: f64,
+    /// The average number of messages in the hours with at least one message.
+    pub messages_per_hour: f64,
+    /// The average number of messages in the (ISO) weeks with at least one
+    /// message.
+    pub messages_per_week: f64,
+    /// The hour of the day with the most messages, see [`peak_activity_hour`].
+    pub peak_hour: u32,
+    /// The day of the week with the most messages, see
+    /// [`most_active_weekday`].
+    pub peak_weekday: Weekday,
+    /// The number of days with at least one message.
+    pub active_days: usize,
+}
+
+impl MessageFrequency {
+    /// Computes the message rates of `messages`. The messages don't need to be
+    /// sorted.
+    ///
+    /// Output is `None` if there are no user messages.
+    pub fn compute(messages: &[Message]) -> Option<MessageFrequency> {
+        let peak_hour = peak_activity_hour(messages)?;
+        let peak_weekday = most_active_weekday(messages)?;
+        let dates: Vec<DateTime<Utc>> = messages.iter().filter(|m| m.is_user()).map(|m| m.date).collect();
+        let total = dates.len() as f64;
+        let active_hours: HashSet<(NaiveDate, u32)> = dates.iter().map(|d| (d.date_naive(), d.hour())).collect();
+        let active_weeks: HashSet<_> = dates.iter().map(|d| d.iso_week()).collect();
+        let active_days = active_days(messages).len();
+
+        Some(MessageFrequency {
+            messages_per_day: total / active_days as f64,
+            messages_per_hour: total / active_hours.len() as f64,
+            messages_per_week: total / active_weeks.len() as f64,
+            peak_hour,
+            peak_weekday,
+            active_days,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, Utc};
+
+    fn message(y: i32, m: u32, d: u32) -> Message {
+        Message {
+            date: Utc.with_ymd_and_hms(y, m, d, 12, 0, 0).unwrap(),
+            author: Some("a".to_string()),
+            message: "m".to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn date(y: i32, m: u32, d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(y, m, d).unwrap()
+    }
+
+    #[test]
+    fn test_group_messages_by_date_year() {
+        let messages = vec![message(2020, 12, 31), message(2021, 1, 1), message(2021, 6, 3), message(2022, 2, 2)];
+        let groups = group_messages_by_date(&messages, DateGranularity::Year);
+
+        assert_eq!(groups.len(), 3);
+        assert_eq!(groups[&date(2020, 1, 1)].len(), 1);
+        assert_eq!(groups[&date(2021, 1, 1)].len(), 2);
+        assert_eq!(groups[&date(2022, 1, 1)].len(), 1);
+    }
+
+    #[test]
+    fn test_group_messages_by_date_week_and_month() {
+        // 2021-06-02 is a Wednesday, 2021-06-07 is the following Monday
+        let messages = vec![message(2021, 6, 2), message(2021, 6, 6), message(2021, 6, 7)];
+
+        let weeks = group_messages_by_date(&messages, DateGranularity::Week);
+        assert_eq!(weeks.keys().copied().collect::<Vec<_>>(), vec![date(2021, 5, 31), date(2021, 6, 7)]);
+        assert_eq!(weeks[&date(2021, 5, 31)].len(), 2);
+
+        let months = group_messages_by_date(&messages, DateGranularity::Month);
+        assert_eq!(months.keys().copied().collect::<Vec<_>>(), vec![date(2021, 6, 1)]);
+
+        let days = group_messages_by_date(&messages, DateGranularity::Day);
+        assert_eq!(days.len(), 3);
+    }
+
+    #[test]
+    fn test_group_messages_by_date_ordering() {
+        let messages = vec![message(2022, 3, 1), message(2020, 1, 5), message(2021, 7, 9), message(2020, 1, 5)];
+        let groups = group_messages_by_date(&messages, DateGranularity::Day);
+        let keys: Vec<_> = groups.keys().copied().collect();
+
+        assert_eq!(keys, vec![date(2020, 1, 5), date(2021, 7, 9), date(2022, 3, 1)]);
+        assert!(std::ptr::eq(groups[&date(2020, 1, 5)][0], &messages[1]));
+        assert!(std::ptr::eq(groups[&date(2020, 1, 5)][1], &messages[3]));
+    }
+
+    fn message_at(h: u32, m: u32) -> Message {
+        Message {
+            date: Utc.with_ymd_and_hms(2021, 6, 3, h, m, 0).unwrap(),
+            author: Some("a".to_string()),
+            message: "m".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_find_gaps() {
+        let messages = vec![message_at(10, 0), message_at(10, 5), message_at(12, 0), message_at(12, 30), message_at(18, 0)];
+
+        let gaps = find_gaps(&messages, Duration::minutes(30));
+        assert_eq!(gaps, vec![(1, 2, Duration::minutes(115)), (3, 4, Duration::minutes(330))]);
+
+        // A gap exactly equal to `min_gap` is not a break
+        let gaps = find_gaps(&messages, Duration::minutes(115));
+        assert_eq!(gaps, vec![(3, 4, Duration::minutes(330))]);
+
+        // Everything within `min_gap` of each other
+        assert!(find_gaps(&messages, Duration::hours(6)).is_empty());
+
+        assert!(find_gaps(&[], Duration::minutes(1)).is_empty());
+        assert!(find_gaps(&messages[..1], Duration::minutes(1)).is_empty());
+    }
+
+    #[test]
+    fn test_largest_gap() {
+        let messages = vec![message_at(10, 0), message_at(11, 0), message_at(12, 0), message_at(12, 30)];
+
+        assert_eq!(largest_gap(&messages), Some((0, 1, Duration::hours(1))));
+        assert_eq!(largest_gap(&messages[2..]), Some((0, 1, Duration::minutes(30))));
+        assert_eq!(largest_gap(&messages[..1]), None);
+        assert_eq!(largest_gap(&[]), None);
+    }
+
+    fn message_from(author: Option<&str>, h: u32, m: u32) -> Message {
+        Message {
+            date: Utc.with_ymd_and_hms(2021, 6, 3, h, m, 0).unwrap(),
+            author: author.map(|a| a.to_string()),
+            message: "m".to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_compute_response_times() {
+        let messages = vec![
+            message_from(Some("A"), 10, 0),
+            message_from(Some("B"), 10, 5),
+            message_from(None, 10, 7),
+            message_from(Some("A"), 10, 20),
+        ];
+        let response_times = compute_response_times(&messages);
+
+        assert_eq!(response_times, vec![Duration::minutes(5), Duration::minutes(15)]);
+        assert_eq!(average_response_time(&messages), Some(Duration::minutes(10)));
+        // Even number of replies, the lower middle value is used
+        assert_eq!(median_response_time(&messages), Some(Duration::minutes(5)));
+    }
+
+    #[test]
+    fn test_compute_response_times_same_author() {
+        let messages = vec![message_from(Some("A"), 10, 0), message_from(Some("A"), 10, 5), message_from(Some("A"), 11, 0)];
+
+        assert!(compute_response_times(&messages).is_empty());
+        assert_eq!(average_response_time(&messages), None);
+        assert_eq!(median_response_time(&messages), None);
+    }
+
+    #[test]
+    fn test_response_time_single_reply() {
+        let messages = vec![message_from(Some("A"), 10, 0), message_from(Some("B"), 10, 5), message_from(Some("B"), 10, 6)];
+
+        assert_eq!(compute_response_times(&messages), vec![Duration::minutes(5)]);
+        assert_eq!(average_response_time(&messages), None);
+        assert_eq!(median_response_time(&messages), None);
+    }
+
+    #[test]
+    fn test_median_response_time_odd() {
+        let messages = vec![
+            message_from(Some("A"), 10, 0),
+            message_from(Some("B"), 10, 30),
+            message_from(Some("A"), 10, 32),
+            message_from(Some("B"), 10, 42),
+        ];
+
+        assert_eq!(median_response_time(&messages), Some(Duration::minutes(10)));
+        assert_eq!(average_response_time(&messages), Some(Duration::minutes(14)));
+    }
+
+    #[test]
+    fn test_response_times_exchange() {
+        let messages = vec![
+            message_from(Some("A"), 10, 0),
+            message_from(Some("B"), 10, 1),
+            message_from(Some("A"), 10, 4),
+            message_from(Some("A"), 10, 5),
+            message_from(Some("B"), 10, 17),
+        ];
+
+        // Replies after 1, 3 and 12 minutes
+        assert_eq!(average_response_time(&messages), Some(Duration::minutes(16) / 3));
+        assert_eq!(median_response_time(&messages), Some(Duration::minutes(3)));
+        // Replies after 1, 3, 12 and 18 minutes
+        let mut messages = messages;
+        messages.push(message_from(Some("A"), 10, 35));
+        assert_eq!(average_response_time(&messages), Some(Duration::seconds(510)));
+        assert_eq!(median_response_time(&messages), Some(Duration::minutes(3)));
+    }
+
+    #[test]
+    fn test_response_time_stats_by_pair() {
+        let messages = vec![
+            message_from(Some("A"), 10, 0),
+            message_from(Some("B"), 10, 2),
+            message_from(Some("A"), 10, 12),
+            message_from(None, 10, 13),
+            message_from(Some("B"), 10, 17),
+            message_from(Some("B"), 10, 18),
+            message_from(Some("A"), 10, 20),
+            message_from(Some("B"), 11, 20),
+        ];
+        let stats = response_time_stats_by_pair(&messages);
+
+        let to_b = stats[&("A".to_string(), "B".to_string())];
+        assert_eq!(to_b.count, 3);
+        assert_eq!(to_b.median, Duration::minutes(5));
+        assert_eq!(to_b.p90, Duration::minutes(60));
+        let to_a = stats[&("B".to_string(), "A".to_string())];
+        assert_eq!(to_a.count, 2);
+        assert_eq!(to_a.median, Duration::minutes(2));
+        assert_eq!(to_a.p90, Duration::minutes(10));
+        assert_eq!(stats.len(), 2);
+        assert!(response_time_stats_by_pair(&messages[..1]).is_empty());
+    }
+
+    #[test]
+    fn test_total_word_count() {
+        let mut messages = vec![message_from(Some("A"), 10, 0), message_from(None, 10, 1), message_from(Some("B"), 10, 2)];
+        messages[0].message = "Hey there".to_string();
+        messages[1].message = "A added B".to_string();
+        messages[2].message = "Hi\nhow are you?".to_string();
+
+        assert_eq!(total_word_count(&messages), 6);
+        assert_eq!(total_word_count(&[]), 0);
+    }
+
+    #[test]
+    fn test_assign_session_ids() {
+        let mut messages = vec![message_at(10, 0), message_at(10, 20), message_at(14, 0), message_at(14, 30)];
+        assign_session_ids(&mut messages, Duration::hours(1));
+
+        let session_ids: Vec<_> = messages.iter().map(|m| m.session_id).collect();
+        assert_eq!(session_ids, vec![Some(0), Some(0), Some(1), Some(1)]);
+    }
+
+    #[test]
+    fn test_total_active_duration() {
+        let messages = vec![
+            message_at(10, 0),
+            message_at(10, 20),
+            message_at(10, 45),
+            message_at(14, 0),
+            message_at(14, 30),
+            message_at(20, 0),
+        ];
+
+        assert_eq!(total_active_duration(&messages, Duration::hours(1)), Duration::minutes(75));
+        assert_eq!(total_active_duration(&messages, Duration::minutes(20)), Duration::minutes(20));
+        assert_eq!(total_active_duration(&messages[..1], Duration::hours(1)), Duration::zero());
+    }
+
+    #[test]
+    fn test_busiest_day_and_hour() {
+        let messages = vec![
+            message_at(9, 0),
+            message_at(22, 10),
+            message_at(22, 30),
+            message_from(None, 22, 40),
+            message_at(23, 50),
+            Message {
+                date: Utc.with_ymd_and_hms(2021, 6, 4, 9, 0, 0).unwrap(),
+                author: Some("a".to_string()),
+                ..Default::default()
+            },
+        ];
+
+        assert_eq!(busiest_day(&messages, &Utc), Some((date(2021, 6, 3), 4)));
+        // Hours 9 and 22 both have two messages, the earliest wins
+        assert_eq!(busiest_hour(&messages, &Utc), Some((9, 2)));
+
+        // Two hours ahead, the late messages move to the next day
+        let tz = FixedOffset::east_opt(2 * 3600).unwrap();
+        assert_eq!(busiest_day(&messages, &tz), Some((date(2021, 6, 4), 4)));
+        assert_eq!(busiest_hour(&messages, &tz), Some((0, 2)));
+
+        assert_eq!(busiest_day(&[], &Utc), None);
+        assert_eq!(busiest_hour(&[message_from(None, 1, 0)], &Utc), None);
+    }
+
+    #[test]
+    fn test_dedup_messages() {
+        let mut messages = vec![message_from(Some("A"), 10, 0), message_from(Some("A"), 10, 0), message_from(Some("A"), 10, 0)];
+        dedup_messages(&mut messages);
+        assert_eq!(messages, vec![message_from(Some("A"), 10, 0)]);
+
+        // Same date and text, sent by different authors or by the system
+        let mut messages = vec![message_from(Some("A"), 10, 0), message_from(Some("B"), 10, 0), message_from(None, 10, 0)];
+        dedup_messages(&mut messages);
+        assert_eq!(messages.len(), 3);
+    }
+
+    #[test]
+    fn test_dedup_messages_stable() {
+        let messages = vec![
+            message_from(Some("B"), 11, 0),
+            message_from(Some("A"), 10, 0),
+            message_from(Some("B"), 11, 0),
+            message_from(Some("C"), 9, 0),
+            message_from(Some("A"), 10, 0),
+        ];
+        let deduped = dedup_messages_stable(messages);
+
+        let authors: Vec<_> = deduped.iter().map(|m| m.author.as_deref().unwrap()).collect();
+        assert_eq!(authors, vec!["B", "A", "C"]);
+        assert!(dedup_messages_stable(Vec::new()).is_empty());
+    }
+
+    #[test]
+    fn test_sort_messages() {
+        let mut messages = vec![message_at(12, 0), message_at(9, 30), message_at(23, 59), message_at(0, 0), message_at(9, 31)];
+
+        sort_messages(&mut messages);
+        let times: Vec<_> = messages.iter().map(|m| m.date.format("%H:%M").to_string()).collect();
+        assert_eq!(times, vec!["00:00", "09:30", "09:31", "12:00", "23:59"]);
+
+        sort_messages_desc(&mut messages);
+        let times: Vec<_> = messages.iter().map(|m| m.date.format("%H:%M").to_string()).collect();
+        assert_eq!(times, vec!["23:59", "12:00", "09:31", "09:30", "00:00"]);
+    }
+
+    #[test]
+    fn test_sort_messages_stability() {
+        let mut messages = vec![message_from(Some("C"), 11, 0), message_from(Some("B"), 10, 0), message_from(Some("A"), 10, 0)];
+
+        sort_messages(&mut messages);
+        let authors: Vec<_> = messages.iter().map(|m| m.author.as_deref().unwrap()).collect();
+        assert_eq!(authors, vec!["B", "A", "C"]);
+
+        sort_messages_desc(&mut messages);
+        let authors: Vec<_> = messages.iter().map(|m| m.author.as_deref().unwrap()).collect();
+        assert_eq!(authors, vec!["C", "B", "A"]);
+    }
+
+    #[test]
+    fn test_compute_author_stats() {
+        let mut messages = vec![
+            message_from(Some("A"), 10, 30),
+            message_from(Some("B"), 10, 5),
+            message_from(None, 10, 7),
+            message_from(Some("A"), 9, 0),
+            message_from(Some("A"), 11, 0),
+        ];
+        messages[0].message = "Hey there".to_string();
+        messages[3].message = "<Media omitted>".to_string();
+        let stats = compute_author_stats(&messages);
+
+        assert_eq!(stats.len(), 2);
+        let a = &stats["A"];
+        assert_eq!(a.name, "A");
+        assert_eq!((a.message_count, a.word_count, a.char_count, a.media_count), (3, 5, 25, 1));
+        assert_eq!(a.first_message, Some(messages[3].date));
+        assert_eq!(a.last_message, Some(messages[4].date));
+        assert_eq!(a.average_message_length, 25.0 / 3.0);
+        assert_eq!(a.activity_ratio(4), 0.75);
+        assert_eq!(stats["B"].first_message, stats["B"].last_message);
+        assert_eq!(stats["B"].activity_ratio(0), 0.0);
+
+        let total: usize = stats.values().map(|s| s.message_count).sum();
+        assert_eq!(total, MessageStats::compute(&messages).user_messages);
+        assert!(compute_author_stats(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_hourly_message_counts() {
+        let messages = vec![
+            message_at(23, 10),
+            message_at(23, 50),
+            message_from(None, 23, 55),
+            Message {
+                date: Utc.with_ymd_and_hms(2021, 6, 4, 0, 5, 0).unwrap(),
+                author: Some("a".to_string()),
+                ..Default::default()
+            },
+            message_at(15, 0),
+            message_at(3, 0),
+        ];
+        let counts = hourly_message_counts(&messages);
+
+        assert_eq!(counts[23], 2);
+        assert_eq!(counts[0], 1);
+        assert_eq!((counts[3], counts[15]), (1, 1));
+        assert_eq!(counts.iter().sum::<usize>(), 5);
+        assert_eq!(peak_activity_hour(&messages), Some(23));
+
+        // Hours 3 and 15 have the same count, the earliest wins
+        assert_eq!(peak_activity_hour(&messages[4..]), Some(3));
+        assert_eq!(hourly_message_counts(&[]), [0; 24]);
+        assert_eq!(peak_activity_hour(&[]), None);
+    }
+
+    #[test]
+    fn test_message_frequency() {
+        let at = |d: u32, h: u32, m: u32| Message {
+            date: Utc.with_ymd_and_hms(2021, 6, d, h, m, 0).unwrap(),
+            ..message_at(h, m)
+        };
+        // Thursday and Friday of the same week, with 4 messages at 10:xx
+        let mut messages = vec![at(3, 10, 0), at(3, 10, 30), at(3, 10, 45), at(3, 14, 0), at(4, 10, 15), at(4, 20, 0), at(4, 20, 30)];
+        messages.push(message_from(None, 20, 45));
+        let frequency = MessageFrequency::compute(&messages).unwrap();
+
+        assert_eq!(frequency.messages_per_day, 3.5);
+        assert_eq!(frequency.messages_per_hour, 1.75);
+        assert_eq!(frequency.messages_per_week, 7.0);
+        assert_eq!(frequency.peak_hour, 10);
+        assert_eq!(frequency.peak_weekday, Weekday::Thu);
+        assert_eq!(frequency.active_days, 2);
+
+        assert_eq!(MessageFrequency::compute(&[]), None);
+        assert_eq!(MessageFrequency::compute(&[message_from(None, 10, 0)]), None);
+    }
+
+    #[test]
+    fn test_weekday_message_counts() {
+        // 2021-06-07 is a Monday, one message on Monday, two on Tuesday...
+        let messages: Vec<_> = (0..7).flat_map(|day| (0..=day).map(move |_| message(2021, 6, 7 + day))).collect();
+        let counts = weekday_message_counts(&messages);
+
+        assert_eq!(counts, [1, 2, 3, 4, 5, 6, 7]);
+        assert_eq!(most_active_weekday(&messages), Some(Weekday::Sun));
+
+        // Tuesdays only
+        let messages = vec![message(2021, 6, 1), message(2021, 6, 8), message(2021, 6, 15)];
+        assert_eq!(weekday_message_counts(&messages), [0, 3, 0, 0, 0, 0, 0]);
+        assert_eq!(most_active_weekday(&messages), Some(Weekday::Tue));
+
+        assert_eq!(weekday_message_counts(&[]), [0; 7]);
+        assert_eq!(most_active_weekday(&[]), None);
+    }
+
+    #[test]
+    fn test_find_longest_and_shortest_messages() {
+        let mut messages: Vec<_> = (0..6).map(|m| message_from(Some("A"), 10, m)).collect();
+        for (message, text) in messages.iter_mut().zip(["ccc", "a", "bbbb", "dd", "eee", "f"]) {
+            message.message = text.to_string();
+        }
+        messages[4].author = None;
+        let texts = |found: Vec<&Message>| found.iter().map(|m| m.message.clone()).collect::<Vec<_>>();
+
+        assert_eq!(texts(find_longest_messages(&messages, 3)), vec!["bbbb", "ccc", "eee"]);
+        assert_eq!(texts(find_shortest_messages(&messages, 3)), vec!["a", "f", "dd"]);
+        assert_eq!(texts(find_longest_messages(&messages, 10)), vec!["bbbb", "ccc", "eee", "dd", "a", "f"]);
+        assert_eq!(texts(find_shortest_messages(&messages, 10)), vec!["a", "f", "dd", "ccc", "eee", "bbbb"]);
+        assert!(find_longest_messages(&messages, 0).is_empty());
+        assert!(find_shortest_messages(&messages, 0).is_empty());
+        assert!(find_longest_messages(&[], 3).is_empty());
+    }
+
+    #[test]
+    fn test_split_into_sessions() {
+        let messages = vec![message_at(10, 0), message_at(10, 20), message_at(14, 0), message_at(15, 0), message_at(20, 0)];
+
+        assert_eq!(split_into_sessions(&messages, Duration::minutes(30)), vec![0..2, 2..3, 3..4, 4..5]);
+        // A gap exactly equal to `session_gap` doesn't split
+        assert_eq!(split_into_sessions(&messages, Duration::hours(1)), vec![0..2, 2..4, 4..5]);
+        assert_eq!(split_into_sessions(&messages, Duration::hours(5)), vec![0..5]);
+
+        let slices = session_slices(&messages, Duration::hours(1));
+        assert_eq!(slices.iter().map(|s| s.len()).collect::<Vec<_>>(), vec![2, 2, 1]);
+        assert!(std::ptr::eq(&slices[1][0], &messages[2]));
+
+        assert_eq!(split_into_sessions(&messages[..1], Duration::minutes(1)), vec![0..1]);
+        assert!(split_into_sessions(&[], Duration::minutes(1)).is_empty());
+        assert!(session_slices(&[], Duration::minutes(1)).is_empty());
+    }
+
+    #[test]
+    fn test_sliding_windows() {
+        let messages = vec![message_at(10, 0), message_at(10, 20), message_at(10, 45), message_at(11, 30), message_at(12, 0)];
+        let windows: Vec<_> = sliding_windows(&messages, Duration::hours(1), Duration::minutes(30)).collect();
+
+        // Windows start at 10:00, 10:30, 11:00, 11:30 and 12:00
+        assert_eq!(windows.len(), 5);
+        let counts: Vec<_> = windows.iter().map(|w| w.messages.len()).collect();
+        assert_eq!(counts, vec![3, 1, 1, 2, 1]);
+        for (idx, window) in windows.iter().enumerate() {
+            assert_eq!(window.window_index, idx);
+            assert_eq!(window.end - window.start, Duration::hours(1));
+            assert!(window.messages.iter().all(|m| window.start <= m.date && m.date < window.end));
+        }
+
+        // Windows larger than the step leave gaps
+        let windows: Vec<_> = sliding_windows(&messages, Duration::minutes(10), Duration::minutes(40)).collect();
+        let counts: Vec<_> = windows.iter().map(|w| w.messages.len()).collect();
+        assert_eq!(counts, vec![1, 1, 0, 1]);
+
+        assert_eq!(sliding_windows(&messages[..1], Duration::hours(1), Duration::hours(1)).count(), 1);
+        assert_eq!(sliding_windows(&[], Duration::hours(1), Duration::hours(1)).count(), 0);
+    }
+
+    #[test]
+    fn test_consecutive_active_days() {
+        let streak: Vec<_> = (1..=5).map(|d| message(2021, 6, d)).collect();
+        assert_eq!(consecutive_active_days(&streak), 5);
+
+        // June 3 is missing, and the system message doesn't count
+        let mut broken = vec![message(2021, 6, 1), message(2021, 6, 2), message(2021, 6, 2), message(2021, 6, 4), message(2021, 6, 5), message(2021, 6, 6)];
+        broken.push(Message {
+            author: None,
+            ..message(2021, 6, 7)
+        });
+        assert_eq!(consecutive_active_days(&broken), 3);
+
+        // Across the end of the month
+        assert_eq!(consecutive_active_days(&[message(2021, 5, 31), message(2021, 6, 1)]), 2);
+        assert_eq!(consecutive_active_days(&[]), 0);
+    }
+
+    #[test]
+    fn test_current_streak() {
+        let messages = vec![message(2021, 6, 1), message(2021, 6, 3), message(2021, 6, 4), message(2021, 6, 5)];
+
+        assert_eq!(current_streak(&messages, date(2021, 6, 5)), 3);
+        // Nothing sent yet today
+        assert_eq!(current_streak(&messages, date(2021, 6, 6)), 3);
+        assert_eq!(current_streak(&messages, date(2021, 6, 7)), 0);
+        // Later messages are ignored
+        assert_eq!(current_streak(&messages, date(2021, 6, 3)), 1);
+        assert_eq!(current_streak(&messages, date(2021, 5, 31)), 0);
+        assert_eq!(current_streak(&[], date(2021, 6, 5)), 0);
+    }
+
+    fn authors_of<'a>(found: &[&'a Message]) -> Vec<Option<&'a str>> {
+        found.iter().map(|m| m.author.as_deref()).collect()
+    }
+
+    #[test]
+    fn test_filter_by_author() {
+        let messages = vec![
+            message_from(Some("Alice"), 10, 0),
+            message_from(Some("Bob"), 10, 1),
+            message_from(None, 10, 2),
+            message_from(Some("Carol"), 10, 3),
+            message_from(Some("alice"), 10, 4),
+        ];
+
+        assert_eq!(authors_of(&filter_by_author(&messages, &["Alice", "Carol"], true)), vec![Some("Alice"), Some("Carol")]);
+        assert_eq!(authors_of(&filter_by_author(&messages, &["alice"], true)), vec![Some("alice")]);
+        assert_eq!(authors_of(&filter_by_author(&messages, &["alice"], false)), vec![Some("Alice"), Some("alice")]);
+        assert_eq!(authors_of(&filter_by_author(&messages, &["BOB"], false)), vec![Some("Bob")]);
+        assert!(filter_by_author(&messages, &[], true).is_empty());
+        assert!(filter_by_author(&messages, &[], false).is_empty());
+    }
+
+    #[test]
+    fn test_exclude_authors() {
+        let messages = vec![message_from(Some("Alice"), 10, 0), message_from(Some("Bob"), 10, 1), message_from(None, 10, 2)];
+
+        assert_eq!(authors_of(&exclude_authors(&messages, &["Alice"])), vec![Some("Bob"), None]);
+        assert_eq!(authors_of(&exclude_authors(&messages, &["alice"])), vec![Some("Alice"), Some("Bob"), None]);
+        assert_eq!(exclude_authors(&messages, &[]).len(), 3);
+    }
+
+    #[test]
+    fn test_filter_by_date_range() {
+        let messages = vec![message_at(10, 0), message_at(10, 30), message_at(10, 30), message_at(11, 0), message_at(12, 0)];
+        let at = |h, m| Utc.with_ymd_and_hms(2021, 6, 3, h, m, 0).unwrap();
+
+        // Both ends are included
+        let found = filter_by_date_range(&messages, at(10, 30), at(11, 0));
+        assert_eq!(found.len(), 3);
+        assert!(std::ptr::eq(&found[0], &messages[1]));
+        assert_eq!(filter_by_date_range(&messages, at(10, 1), at(10, 29)).len(), 0);
+        assert_eq!(filter_by_date_range(&messages, at(9, 0), at(13, 0)).len(), 5);
+        assert_eq!(filter_by_date_range(&messages, at(12, 0), at(12, 0)).len(), 1);
+        assert!(filter_by_date_range(&messages, at(11, 0), at(10, 0)).is_empty());
+        assert!(filter_by_date_range(&[], at(10, 0), at(11, 0)).is_empty());
+
+        let unsorted = vec![message_at(12, 0), message_at(10, 30), message_at(9, 0), message_at(11, 0)];
+        let found = filter_by_date_range_vec(&unsorted, at(10, 30), at(12, 0));
+        assert_eq!(found.iter().map(|m| m.date).collect::<Vec<_>>(), vec![at(12, 0), at(10, 30), at(11, 0)]);
+        assert!(filter_by_date_range_vec(&unsorted, at(11, 0), at(10, 0)).is_empty());
+    }
+}
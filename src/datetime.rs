@@ -1,6 +1,25 @@
+use crate::error::{DateError, DateFormatError, TimeParseError};
+use chrono::{NaiveDate, NaiveDateTime};
 use regex::Regex;
+use std::borrow::Cow;
 use std::collections::HashMap;
 
+/// Finds the date at the start of a message line, as an escape hatch for
+/// formats the built-in parsing doesn't support.
+///
+/// Set it as `date_time_parser` in the options to replace the built-in date
+/// detection and normalization, while keeping the handling of authors,
+/// bodies and multiline messages. See `parser::MonthNameDateTimeParser` for
+/// an implementation.
+pub trait DateTimeParser: Send + Sync {
+    /// Parses the date at the start of `header`, a line of the export.
+    ///
+    /// Returns the date and the number of bytes it takes up, including the
+    /// separator before the author or body, or `None` if the line doesn't
+    /// start a message.
+    fn parse(&self, header: &str) -> Option<(NaiveDateTime, usize)>;
+}
+
 /// Takes an array of numeric dates and tries to understand if the days come
 /// before the month or the other way around by checking if numbers go above
 /// `12`.
@@ -45,10 +64,10 @@ pub fn check_decreasing(numeric_dates: &[Vec<i32>]) -> Option<bool> {
         })
         .collect();
 
-    if results.iter().any(|&r| r == Some(true)) {
+    if results.contains(&Some(true)) {
         return Some(true);
     }
-    if results.iter().any(|&r| r == Some(false)) {
+    if results.contains(&Some(false)) {
         return Some(false);
     }
 
@@ -88,6 +107,37 @@ pub fn change_frequency_analysis(numeric_dates: &[Vec<i32>]) -> Option<bool> {
     None
 }
 
+/// Takes an array of numeric dates and tries to understand if the days come
+/// before the month or the other way around by checking which interpretation
+/// gives dates that exist in the calendar.
+///
+/// Two digit years are expanded as in [`normalize_date`].
+///
+/// Only dates with a number above `12` can be impossible in one order, so this
+/// never decides more than [`check_above_12`], and always agrees with it. It's
+/// not one of the checks of [`days_before_months`] for that reason, but it
+/// tells whether a set of dates only exists in the calendar in one order.
+///
+/// Output is `true` if days are first, `false` if they are second, or `None` if
+/// it failed to understand the order.
+pub fn check_calendar_validity(numeric_dates: &[Vec<i32>]) -> Option<bool> {
+    let is_valid = |day: i32, month: i32, year: i32| {
+        let year = expand_two_digit_year(year, DEFAULT_TWO_DIGIT_YEAR_CUTOFF);
+        let (Ok(day), Ok(month)) = (u32::try_from(day), u32::try_from(month)) else {
+            return false;
+        };
+        NaiveDate::from_ymd_opt(year, month, day).is_some()
+    };
+    let days_first_valid = numeric_dates.iter().all(|d| is_valid(d[0], d[1], d[2]));
+    let months_first_valid = numeric_dates.iter().all(|d| is_valid(d[1], d[0], d[2]));
+
+    match (days_first_valid, months_first_valid) {
+        (true, false) => Some(true),
+        (false, true) => Some(false),
+        _ => None,
+    }
+}
+
 /// Takes an array of numeric dates and tries to understand if the days come
 /// before the month or the other way around by running the dates through various
 /// checks.
@@ -100,83 +150,125 @@ pub fn days_before_months(numeric_dates: &[Vec<i32>]) -> Option<bool> {
         .or_else(|| change_frequency_analysis(numeric_dates))
 }
 
+/// The default of `ParseStringOptions::two_digit_year_cutoff`: two digit years
+/// below it are in the 2000s, the others in the 1900s, as in POSIX `strptime`.
+pub const DEFAULT_TWO_DIGIT_YEAR_CUTOFF: u8 = 70;
+
+/// Expands a two digit year to four digits: years below `cutoff` are in the
+/// 2000s, the others in the 1900s. Longer years are returned as they are.
+pub fn expand_two_digit_year(year: i32, cutoff: u8) -> i32 {
+    match year {
+        0..=99 if year < i32::from(cutoff) => 2000 + year,
+        0..=99 => 1900 + year,
+        _ => year,
+    }
+}
+
 /// Takes `year`, `month` and `day` as strings and pads them to `4`, `2`, `2`
 /// digits respectively.
-pub fn normalize_date(year: &str, month: &str, day: &str) -> (String, String, String) {
-    // 2 digit years are assumed to be in the 2000-2099 range
-    let normalized_year = if year.len() <= 2 {
-        format!("20{:0>2}", year)
-    } else {
-        year.to_string()
+///
+/// Two digit years are expanded with [`DEFAULT_TWO_DIGIT_YEAR_CUTOFF`], see
+/// [`normalize_date_with_cutoff`].
+///
+/// Fails with `DateError::NonNumericComponent` if a component is not made of
+/// ASCII digits, e.g. `Jan`.
+pub fn normalize_date(year: &str, month: &str, day: &str) -> Result<(String, String, String), DateError> {
+    normalize_date_with_cutoff(year, month, day, DEFAULT_TWO_DIGIT_YEAR_CUTOFF)
+}
+
+/// Same as [`normalize_date`], but two digit years below `cutoff` are in the
+/// 2000s and the others in the 1900s.
+pub fn normalize_date_with_cutoff(year: &str, month: &str, day: &str, cutoff: u8) -> Result<(String, String, String), DateError> {
+    if let Some(component) = [year, month, day].into_iter().find(|c| c.is_empty() || !c.bytes().all(|b| b.is_ascii_digit())) {
+        return Err(DateError::NonNumericComponent(component.to_string()));
+    }
+    let normalized_year = match year.parse::<i32>() {
+        Ok(short) if year.len() <= 2 => expand_two_digit_year(short, cutoff).to_string(),
+        _ => year.to_string(),
     };
 
-    (
+    Ok((
         normalized_year,
         format!("{:0>2}", month),
         format!("{:0>2}", day),
-    )
+    ))
 }
 
 /// Pushes the longest number in a date to the end, if there is one. Necessary to
 /// ensure the year is the last number.
-pub fn order_date_components(date: &str) -> (String, String, String) {
+///
+/// Fails with `DateFormatError::TooFewComponents` if the date has fewer than
+/// three components, e.g. `2018-06`.
+pub fn order_date_components(date: &str) -> Result<(String, String, String), DateFormatError> {
     let parts: Vec<&str> = date
-        .split(|c| c == '-' || c == '/' || c == '.')
+        .split(['-', '/', '.'])
         .map(|s| s.trim())
         .collect();
-    let a = parts[0];
-    let b = parts[1];
-    let c = parts[2];
+    let [a, b, c, ..] = parts[..] else {
+        return Err(DateFormatError::TooFewComponents { found: parts.len() });
+    };
 
     let max_len = a.len().max(b.len()).max(c.len());
 
-    if c.len() == max_len {
+    Ok(if c.len() == max_len {
         (a.to_string(), b.to_string(), c.to_string())
     } else if b.len() == max_len {
         (a.to_string(), c.to_string(), b.to_string())
     } else {
         (b.to_string(), c.to_string(), a.to_string())
-    }
+    })
 }
 
 /// Converts time from 12 hour format to 24 hour format.
-pub fn convert_time_12_to_24(time: &str, ampm: &str) -> String {
+///
+/// Hours above 12 are already in 24 hour format and kept as they are, whatever
+/// the value of `ampm`.
+///
+/// Fails with `TimeParseError::InvalidHour` if the hours are not a number, or
+/// `TimeParseError::MissingMinutes` if there are no minutes.
+pub fn convert_time_12_to_24(time: &str, ampm: &str) -> Result<String, TimeParseError> {
     let re = Regex::new(r"[:.]").unwrap();
     let parts: Vec<&str> = re.split(time).collect();
 
-    let mut hours = parts[0].parse::<i32>().unwrap();
-    let minutes = parts[1];
+    let mut hours = parts[0]
+        .parse::<i32>()
+        .map_err(|_| TimeParseError::InvalidHour(parts[0].to_string()))?;
+    let minutes = *parts.get(1).ok_or_else(|| TimeParseError::MissingMinutes(time.to_string()))?;
     let seconds = if parts.len() > 2 {
         Some(parts[2])
     } else {
         None
     };
 
-    if hours == 12 {
-        hours = 0;
-    }
+    if hours <= 12 {
+        if hours == 12 {
+            hours = 0;
+        }
 
-    if ampm == "PM" {
-        hours += 12;
+        if ampm == "PM" {
+            hours += 12;
+        }
     }
 
-    if let Some(seconds) = seconds {
+    Ok(if let Some(seconds) = seconds {
         format!("{:02}:{}:{}", hours, minutes, seconds)
     } else {
         format!("{:02}:{}", hours, minutes)
-    }
+    })
 }
 
 /// Normalizes a time string to have the following format: `hh:mm:ss`.
+///
+/// Missing minutes and seconds are `00`.
 pub fn normalize_time(time: &str) -> String {
     let re = Regex::new(r"[:.]").unwrap();
     let parts: Vec<&str> = re.split(time).collect();
 
     let hours = parts[0];
-    let minutes = parts[1];
+    let minutes = parts.get(1).copied().unwrap_or("00");
     let seconds = if parts.len() > 2 { parts[2] } else { "00" };
 
-    format!("{:0>2}:{}:{}", hours, minutes, seconds)
+    format!("{:0>2}:{:0>2}:{:0>2}", hours, minutes, seconds)
 }
 
 /// Normalizes `am` / `a.m.` / etc. to `AM` (uppercase, no other characters).
@@ -185,6 +277,32 @@ pub fn normalize_ampm(ampm: &str) -> String {
         .to_uppercase()
 }
 
+/// The zero of the decimal digit sets, other than ASCII, that can appear in
+/// dates: Arabic-Indic, Extended Arabic-Indic (Persian), Devanagari, Bengali,
+/// Thai, Myanmar and fullwidth digits.
+const ZERO_DIGITS: [u32; 7] = [0x0660, 0x06F0, 0x0966, 0x09E6, 0x0E50, 0x1040, 0xFF10];
+
+/// Replaces the non-ASCII decimal digits (e.g. Arabic-Indic `٢٠١٨`) with their
+/// ASCII counterparts, so dates and times can be parsed as numbers.
+///
+/// Strings with only ASCII characters are returned as they are.
+pub fn normalize_digits(s: &str) -> Cow<'_, str> {
+    if s.is_ascii() {
+        return Cow::Borrowed(s);
+    }
+
+    s.chars()
+        .map(|c| {
+            ZERO_DIGITS
+                .iter()
+                .find(|zero| (**zero..**zero + 10).contains(&(c as u32)))
+                .and_then(|zero| char::from_digit(c as u32 - zero, 10))
+                .unwrap_or(c)
+        })
+        .collect::<String>()
+        .into()
+}
+
 /// Checks that the number at a certain index of an array is greater than a
 /// certain value.
 pub fn index_above_value(index: usize, value: i32) -> impl Fn(&[i32]) -> bool {
@@ -200,18 +318,53 @@ pub fn is_negative(number: i32) -> bool {
 
 /// Takes an array of arrays and an index and groups the inner arrays by the
 /// value at the index provided.
-pub fn group_array_by_value_at_index<T: Clone>(array: &[Vec<T>], index: usize) -> Vec<Vec<Vec<T>>>
+///
+/// Groups are in the order of the first occurrence of their value in the array,
+/// and keep the order of their inner arrays.
+pub fn group_array_by_value_at_index<T>(array: &[Vec<T>], index: usize) -> Vec<Vec<Vec<T>>>
 where
-    T: std::cmp::Eq + std::hash::Hash + ToString,
+    T: Clone + std::cmp::Eq + std::hash::Hash,
 {
-    let mut map: HashMap<String, Vec<Vec<T>>> = HashMap::new();
+    let mut group_of_value: HashMap<&T, usize> = HashMap::new();
+    let mut groups: Vec<Vec<Vec<T>>> = Vec::new();
 
     for item in array {
-        let key = format!("_{}", item[index].to_string());
-        map.entry(key).or_default().push(item.clone());
+        let group = *group_of_value.entry(&item[index]).or_insert_with(|| {
+            groups.push(Vec::new());
+            groups.len() - 1
+        });
+        groups[group].push(item.clone());
     }
 
-    map.into_values().collect()
+    groups
+}
+
+/// Returns the month names written in the exports of a locale, lowercase,
+/// mapped to the number of the month (`1` for January).
+///
+/// Only the language of `bcp47` is used, so `"pt-BR"` and `"pt"` give the same
+/// names. Covers German (`de`), French (`fr`), Spanish (`es`), Portuguese
+/// (`pt`), Italian (`it`) and Dutch (`nl`); output is `None` for other
+/// languages. Names with accents are also listed without them.
+pub fn month_map_for_locale(bcp47: &str) -> Option<HashMap<String, u32>> {
+    let language = bcp47.split(['-', '_']).next().unwrap_or("").to_lowercase();
+    let names: &[&[&str]] = match language.as_str() {
+        "de" => &[&["januar"], &["februar"], &["märz", "maerz"], &["april"], &["mai"], &["juni"], &["juli"], &["august"], &["september"], &["oktober"], &["november"], &["dezember"]],
+        "fr" => &[&["janvier"], &["février", "fevrier"], &["mars"], &["avril"], &["mai"], &["juin"], &["juillet"], &["août", "aout"], &["septembre"], &["octobre"], &["novembre"], &["décembre", "decembre"]],
+        "es" => &[&["enero"], &["febrero"], &["marzo"], &["abril"], &["mayo"], &["junio"], &["julio"], &["agosto"], &["septiembre", "setiembre"], &["octubre"], &["noviembre"], &["diciembre"]],
+        "pt" => &[&["janeiro"], &["fevereiro"], &["março", "marco"], &["abril"], &["maio"], &["junho"], &["julho"], &["agosto"], &["setembro"], &["outubro"], &["novembro"], &["dezembro"]],
+        "it" => &[&["gennaio"], &["febbraio"], &["marzo"], &["aprile"], &["maggio"], &["giugno"], &["luglio"], &["agosto"], &["settembre"], &["ottobre"], &["novembre"], &["dicembre"]],
+        "nl" => &[&["januari"], &["februari"], &["maart"], &["april"], &["mei"], &["juni"], &["juli"], &["augustus"], &["september"], &["oktober"], &["november"], &["december"]],
+        _ => return None,
+    };
+
+    Some(
+        names
+            .iter()
+            .zip(1..)
+            .flat_map(|(spellings, month)| spellings.iter().map(move |name| (name.to_string(), month)))
+            .collect(),
+    )
 }
 
 #[cfg(test)]
@@ -257,22 +410,94 @@ mod tests {
         assert_eq!(change_frequency_analysis(&undetectable), None);
     }
 
+    #[test]
+    fn test_check_calendar_validity() {
+        let days_first = vec![vec![30, 1, 2022], vec![29, 2, 2024]];
+        let months_first = vec![vec![2, 14, 2022], vec![4, 16, 2022]];
+        let undetectable = vec![vec![4, 6, 2022], vec![11, 10, 2022]];
+        // February 29 only exists in leap years
+        let not_leap_year = vec![vec![2, 29, 23]];
+
+        assert_eq!(check_calendar_validity(&days_first), Some(true));
+        assert_eq!(check_calendar_validity(&months_first), Some(false));
+        assert_eq!(check_calendar_validity(&undetectable), None);
+        assert_eq!(check_calendar_validity(&not_leap_year), None);
+        assert_eq!(check_calendar_validity(&[vec![2, 29, 24]]), Some(false));
+    }
+
+    #[test]
+    fn test_check_calendar_validity_agrees_with_check_above_12() {
+        for a in 0..=32 {
+            for b in 0..=32 {
+                let dates = vec![vec![a, b, 2024], vec![4, 6, 23]];
+                if let Some(days_first) = check_calendar_validity(&dates) {
+                    assert_eq!(check_above_12(&dates), Some(days_first), "{:?}", dates);
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_normalize_date() {
         let expected = ("2011".to_string(), "03".to_string(), "04".to_string());
 
-        assert_eq!(normalize_date("11", "3", "4"), expected);
-        assert_eq!(normalize_date("2011", "03", "04"), expected);
+        assert_eq!(normalize_date("11", "3", "4"), Ok(expected.clone()));
+        assert_eq!(normalize_date("2011", "03", "04"), Ok(expected));
+        assert_eq!(normalize_date("2011", "Jan", "04"), Err(DateError::NonNumericComponent("Jan".to_string())));
+        assert_eq!(normalize_date("xx", "03", "04"), Err(DateError::NonNumericComponent("xx".to_string())));
+        assert_eq!(normalize_date("2011", "03", ""), Err(DateError::NonNumericComponent(String::new())));
+    }
+
+    #[test]
+    fn test_normalize_date_with_cutoff() {
+        let year = |y: &str, cutoff: u8| normalize_date_with_cutoff(y, "1", "1", cutoff).unwrap().0;
+
+        assert_eq!(year("69", 70), "2069");
+        assert_eq!(year("70", 70), "1970");
+        assert_eq!(year("99", 70), "1999");
+        assert_eq!(year("00", 70), "2000");
+        assert_eq!(year("5", 70), "2005");
+        assert_eq!(year("2075", 70), "2075");
+        assert_eq!(year("69", 50), "1969");
+        assert_eq!(year("49", 50), "2049");
+        assert_eq!(normalize_date("69", "1", "1").unwrap().0, "2069");
+    }
+
+    #[test]
+    fn test_order_date_components() {
+        let ordered = |d: &str, m: &str, y: &str| Ok((d.to_string(), m.to_string(), y.to_string()));
+
+        assert_eq!(order_date_components("23/06/2018"), ordered("23", "06", "2018"));
+        assert_eq!(order_date_components("6/23/18"), ordered("6", "23", "18"));
+        assert_eq!(order_date_components("23.06.18"), ordered("23", "06", "18"));
+        assert_eq!(order_date_components("2018-06-23"), ordered("06", "23", "2018"));
+        assert_eq!(order_date_components("23. 6. 2018"), ordered("23", "6", "2018"));
+        assert_eq!(order_date_components("2018-06"), Err(DateFormatError::TooFewComponents { found: 2 }));
+        assert_eq!(order_date_components(""), Err(DateFormatError::TooFewComponents { found: 1 }));
     }
 
     #[test]
     fn test_convert_time_12_to_24() {
-        assert_eq!(convert_time_12_to_24("12:00", "PM"), "12:00");
-        assert_eq!(convert_time_12_to_24("12:00", "AM"), "00:00");
-        assert_eq!(convert_time_12_to_24("05:06", "PM"), "17:06");
-        assert_eq!(convert_time_12_to_24("07:19", "AM"), "07:19");
-        assert_eq!(convert_time_12_to_24("01:02:34", "PM"), "13:02:34");
-        assert_eq!(convert_time_12_to_24("02:04:54", "AM"), "02:04:54");
+        assert_eq!(convert_time_12_to_24("12:00", "PM").unwrap(), "12:00");
+        assert_eq!(convert_time_12_to_24("12:00", "AM").unwrap(), "00:00");
+        assert_eq!(convert_time_12_to_24("05:06", "PM").unwrap(), "17:06");
+        assert_eq!(convert_time_12_to_24("07:19", "AM").unwrap(), "07:19");
+        assert_eq!(convert_time_12_to_24("01:02:34", "PM").unwrap(), "13:02:34");
+        assert_eq!(convert_time_12_to_24("02:04:54", "AM").unwrap(), "02:04:54");
+        assert_eq!(convert_time_12_to_24("13:00", "PM").unwrap(), "13:00");
+        assert_eq!(convert_time_12_to_24("23:15", "AM").unwrap(), "23:15");
+        assert_eq!(convert_time_12_to_24("xx:15", "AM"), Err(TimeParseError::InvalidHour("xx".to_string())));
+        assert_eq!(convert_time_12_to_24("Jan", "PM"), Err(TimeParseError::InvalidHour("Jan".to_string())));
+        assert_eq!(convert_time_12_to_24("11", "PM"), Err(TimeParseError::MissingMinutes("11".to_string())));
+    }
+
+    #[test]
+    fn test_normalize_digits() {
+        assert_eq!(normalize_digits("23/06/2018"), "23/06/2018");
+        assert_eq!(normalize_digits("٢٣/٠٦/٢٠١٨"), "23/06/2018");
+        assert_eq!(normalize_digits("۱۳:۴۵"), "13:45");
+        assert_eq!(normalize_digits("２３.０６"), "23.06");
+        assert_eq!(normalize_digits("١٣:٤٥ م"), "13:45 م");
     }
 
     #[test]
@@ -292,6 +517,9 @@ mod tests {
         assert_eq!(normalize_time("12:34"), "12:34:00");
         assert_eq!(normalize_time("1:23:45"), "01:23:45");
         assert_eq!(normalize_time("12:34:56"), "12:34:56");
+        assert_eq!(normalize_time("9:5"), "09:05:00");
+        assert_eq!(normalize_time("9:05:7"), "09:05:07");
+        assert_eq!(normalize_time("13"), "13:00:00");
     }
 
     #[test]
@@ -309,12 +537,12 @@ mod tests {
     fn test_is_negative() {
         assert!(is_negative(-1));
         assert!(is_negative(-15));
-        assert!(is_negative(std::i32::MIN));
+        assert!(is_negative(i32::MIN));
 
         assert!(!is_negative(0));
         assert!(!is_negative(1));
         assert!(!is_negative(15));
-        assert!(!is_negative(std::i32::MAX));
+        assert!(!is_negative(i32::MAX));
     }
 
     #[test]
@@ -334,4 +562,52 @@ mod tests {
         let grouped_by_2 = group_array_by_value_at_index(&array, 2);
         assert_eq!(grouped_by_2.len(), 1);
     }
+
+    #[test]
+    fn test_group_array_by_value_at_index_order() {
+        let array = vec![vec![1, 5, 2019], vec![2, 5, 2017], vec![3, 5, 2019], vec![4, 5, 2018], vec![5, 5, 2017]];
+        let grouped = group_array_by_value_at_index(&array, 2);
+
+        assert_eq!(
+            grouped,
+            vec![
+                vec![vec![1, 5, 2019], vec![3, 5, 2019]],
+                vec![vec![2, 5, 2017], vec![5, 5, 2017]],
+                vec![vec![4, 5, 2018]],
+            ]
+        );
+    }
+
+    #[test]
+    fn test_group_array_by_value_at_index_first_occurrence() {
+        // Enough values for a hash based order to differ from the input one
+        let years: Vec<i32> = (0..64).map(|i| 2100 - (i * 37) % 64).collect();
+        let array: Vec<Vec<i32>> = years.iter().chain(years.iter().rev()).enumerate().map(|(i, &year)| vec![i as i32, 1, year]).collect();
+        let grouped = group_array_by_value_at_index(&array, 2);
+
+        assert_eq!(grouped.iter().map(|group| group[0][2]).collect::<Vec<_>>(), years);
+        for group in &grouped {
+            assert_eq!(group.len(), 2);
+            assert!(group[0][0] < group[1][0]);
+        }
+    }
+
+    #[test]
+    fn test_month_map_for_locale() {
+        let german = month_map_for_locale("de").unwrap();
+        assert_eq!(german["januar"], 1);
+        assert_eq!((german["märz"], german["maerz"]), (3, 3));
+        assert_eq!(german["dezember"], 12);
+
+        assert_eq!(month_map_for_locale("pt-BR"), month_map_for_locale("pt"));
+        assert_eq!(month_map_for_locale("pt-BR").unwrap()["fevereiro"], 2);
+        assert_eq!(month_map_for_locale("FR").unwrap()["août"], 8);
+        assert_eq!(month_map_for_locale("es").unwrap()["enero"], 1);
+        for locale in ["de", "fr", "es", "pt-BR", "it", "nl"] {
+            let months = month_map_for_locale(locale).unwrap();
+            assert_eq!(months.values().collect::<std::collections::HashSet<_>>().len(), 12, "{}", locale);
+        }
+        assert_eq!(month_map_for_locale("ja"), None);
+        assert_eq!(month_map_for_locale(""), None);
+    }
 }
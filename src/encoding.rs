@@ -0,0 +1,68 @@
+//! Decoding of chat exports that are not encoded in UTF-8.
+
+use std::borrow::Cow;
+
+/// The characters of the bytes `0x80` to `0x9F` in Windows-1252. The other
+/// bytes map to the Unicode code point of the same value, as in Latin-1.
+/// Bytes left undefined by Windows-1252 are kept as the matching C1 control.
+const WINDOWS_1252_HIGH: [char; 32] = [
+    '\u{20AC}', '\u{81}', '\u{201A}', '\u{192}', '\u{201E}', '\u{2026}', '\u{2020}', '\u{2021}',
+    '\u{2C6}', '\u{2030}', '\u{160}', '\u{2039}', '\u{152}', '\u{8D}', '\u{17D}', '\u{8F}',
+    '\u{90}', '\u{2018}', '\u{2019}', '\u{201C}', '\u{201D}', '\u{2022}', '\u{2013}', '\u{2014}',
+    '\u{2DC}', '\u{2122}', '\u{161}', '\u{203A}', '\u{153}', '\u{9D}', '\u{17E}', '\u{178}',
+];
+
+/// Decodes Windows-1252 bytes, which also covers Latin-1 text.
+fn decode_windows_1252(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .map(|&b| match b {
+            0x80..=0x9F => WINDOWS_1252_HIGH[(b - 0x80) as usize],
+            _ => b as char,
+        })
+        .collect()
+}
+
+/// Decodes UTF-16 bytes without their byte order mark, replacing invalid
+/// sequences with U+FFFD.
+fn decode_utf16(bytes: &[u8], little_endian: bool) -> String {
+    let units = bytes.chunks_exact(2).map(|pair| {
+        if little_endian {
+            u16::from_le_bytes([pair[0], pair[1]])
+        } else {
+            u16::from_be_bytes([pair[0], pair[1]])
+        }
+    });
+    char::decode_utf16(units)
+        .map(|c| c.unwrap_or(char::REPLACEMENT_CHARACTER))
+        .collect()
+}
+
+/// Decodes the contents of a chat export: as UTF-8 if valid, then according to
+/// a UTF-16 byte order mark, and as Windows-1252 as a last resort.
+///
+/// Valid UTF-8 is borrowed without copying.
+pub fn decode(bytes: &[u8]) -> Cow<'_, str> {
+    if let Ok(text) = std::str::from_utf8(bytes) {
+        return Cow::Borrowed(text);
+    }
+
+    match bytes {
+        [0xFF, 0xFE, rest @ ..] => decode_utf16(rest, true).into(),
+        [0xFE, 0xFF, rest @ ..] => decode_utf16(rest, false).into(),
+        _ => decode_windows_1252(bytes).into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        assert!(matches!(decode("héllo".as_bytes()), Cow::Borrowed("héllo")));
+        assert_eq!(decode(b"caf\xE9 \x80 \x96 \x93"), "café € – \u{201C}");
+        assert_eq!(decode(b"\xFF\xFEh\x00\xE9\x00"), "hé");
+        assert_eq!(decode(b"\xFE\xFF\x00h\x00\xE9"), "hé");
+    }
+}
@@ -0,0 +1,192 @@
+use std::fmt;
+
+/// A size limit set in [`ParseStringOptions`](crate::models::ParseStringOptions).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SizeLimit {
+    /// The `max_bytes` limit, in bytes.
+    Bytes(usize),
+    /// The `max_lines` limit, in lines.
+    Lines(usize),
+}
+
+/// Errors that can stop a chat export from being parsed.
+#[derive(Debug)]
+pub enum ParseError {
+    /// The input is larger than one of the limits set in the options. Carries
+    /// the limit that was exceeded.
+    TooLarge(SizeLimit),
+    /// A message doesn't have the expected format. Carries the raw message.
+    MalformedMessage(String),
+    /// The input is not valid UTF-8. The wrapped error carries the offset of
+    /// the first invalid byte.
+    Utf8Error(std::str::Utf8Error),
+    /// The input could not be read.
+    Io(std::io::Error),
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::TooLarge(SizeLimit::Bytes(max)) => {
+                write!(f, "input is larger than the limit of {} bytes", max)
+            }
+            ParseError::TooLarge(SizeLimit::Lines(max)) => {
+                write!(f, "input is longer than the limit of {} lines", max)
+            }
+            ParseError::MalformedMessage(msg) => {
+                write!(f, "message doesn't have the expected format: '{}'", msg)
+            }
+            ParseError::Utf8Error(err) => write!(f, "input is not valid UTF-8: {}", err),
+            ParseError::Io(err) => write!(f, "input could not be read: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ParseError::Utf8Error(err) => Some(err),
+            ParseError::Io(err) => Some(err),
+            _ => None,
+        }
+    }
+}
+
+impl From<std::io::Error> for ParseError {
+    fn from(err: std::io::Error) -> Self {
+        ParseError::Io(err)
+    }
+}
+
+impl From<std::str::Utf8Error> for ParseError {
+    fn from(err: std::str::Utf8Error) -> Self {
+        ParseError::Utf8Error(err)
+    }
+}
+
+/// Errors returned by the date helpers of [`datetime`](crate::datetime) for
+/// dates that don't have the expected shape.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DateFormatError {
+    /// The date has fewer than the three expected components (day, month and
+    /// year). Carries the number of components found.
+    TooFewComponents { found: usize },
+}
+
+impl fmt::Display for DateFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateFormatError::TooFewComponents { found } => {
+                write!(f, "date has {} components instead of 3", found)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DateFormatError {}
+
+/// Errors returned by [`normalize_date`](crate::datetime::normalize_date) for
+/// dates that are not made of numbers.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DateError {
+    /// A component of the date is not a number. Carries the component.
+    NonNumericComponent(String),
+}
+
+impl fmt::Display for DateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DateError::NonNumericComponent(component) => {
+                write!(f, "date component '{}' is not a number", component)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DateError {}
+
+/// Errors returned by
+/// [`convert_time_12_to_24`](crate::datetime::convert_time_12_to_24) for times
+/// that don't have the expected shape.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TimeParseError {
+    /// The hours of the time are not a number. Carries the hours.
+    InvalidHour(String),
+    /// The time has no minutes. Carries the time.
+    MissingMinutes(String),
+}
+
+impl fmt::Display for TimeParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            TimeParseError::InvalidHour(hours) => write!(f, "hours '{}' are not a number", hours),
+            TimeParseError::MissingMinutes(time) => write!(f, "time '{}' has no minutes", time),
+        }
+    }
+}
+
+impl std::error::Error for TimeParseError {}
+
+/// Why a line of the input could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseLineErrorReason {
+    /// The line does not start a message and there is no previous message it
+    /// could be the continuation of.
+    NoPrecedingMessage,
+    /// The line starts a message with an impossible date or time, e.g.
+    /// February 30.
+    InvalidDate,
+}
+
+/// A line of the input that could not be parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLineError {
+    /// The number of the line in the input, starting from `1`.
+    pub line_number: usize,
+    /// The content of the line.
+    pub raw_line: String,
+    /// Why the line could not be parsed.
+    pub reason: ParseLineErrorReason,
+}
+
+impl fmt::Display for ParseLineError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self.reason {
+            ParseLineErrorReason::NoPrecedingMessage => "line is not part of any message",
+            ParseLineErrorReason::InvalidDate => "invalid date",
+        };
+        write!(f, "line {}: {}", self.line_number, reason)
+    }
+}
+
+impl std::error::Error for ParseLineError {}
+
+/// A problem found while parsing that didn't stop the chat from being parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseWarning {
+    /// The order of the date components was not set in the options and could
+    /// not be detected, so `preferred_order` was used.
+    DefaultedDateOrder,
+    /// A line of the input was left out of the parsed messages.
+    DroppedLine(ParseLineError),
+    /// A message that doesn't have the expected format was skipped. Carries the
+    /// raw message.
+    SkippedMessage(String),
+    /// A message has an impossible date or time (e.g. February 30), which was
+    /// replaced with 1970-01-01 or midnight. Carries the first line of the
+    /// message and its number.
+    InvalidDate(ParseLineError),
+}
+
+impl fmt::Display for ParseWarning {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseWarning::DefaultedDateOrder => {
+                write!(f, "date order could not be detected, used the preferred order")
+            }
+            ParseWarning::DroppedLine(err) => write!(f, "{}", err),
+            ParseWarning::SkippedMessage(msg) => write!(f, "skipped message: '{}'", msg),
+            ParseWarning::InvalidDate(err) => write!(f, "{}, used 1970-01-01 instead", err),
+        }
+    }
+}
@@ -0,0 +1,463 @@
+use crate::analysis::{group_messages_by_date, DateGranularity};
+use crate::models::Message;
+
+/// The line ending used when formatting messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum LineEnding {
+    /// `\n`, as used by Android and most Unix tools.
+    #[default]
+    Lf,
+    /// `\r\n`, as used by Windows.
+    CrLf,
+}
+
+impl LineEnding {
+    /// Returns the characters making up the line ending.
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LineEnding::Lf => "\n",
+            LineEnding::CrLf => "\r\n",
+        }
+    }
+}
+
+/// Describes how messages are turned back into the lines of a chat export.
+#[derive(Debug, Clone)]
+pub struct MessageFormat {
+    /// The `chrono` format string used for the date and time of each message.
+    ///
+    /// Defaults to `%d/%m/%Y, %H:%M`, which the parser reads back as day-first.
+    pub date_format: String,
+    /// The line ending written after each line, including the inner lines of
+    /// multiline messages.
+    pub line_ending: LineEnding,
+}
+
+impl Default for MessageFormat {
+    fn default() -> Self {
+        MessageFormat {
+            date_format: "%d/%m/%Y, %H:%M".to_string(),
+            line_ending: LineEnding::Lf,
+        }
+    }
+}
+
+/// Formats a single message as a chat export line: `date - author: message` for
+/// user messages and `date - message` for system messages.
+///
+/// Multiline messages keep their inner line breaks, converted to the line
+/// ending of the format. No line ending is added at the end.
+pub fn format_message(message: &Message, format: &MessageFormat) -> String {
+    let date = message.date.format(&format.date_format);
+    let body = message
+        .message
+        .split('\n')
+        .map(|line| line.strip_suffix('\r').unwrap_or(line))
+        .collect::<Vec<_>>()
+        .join(format.line_ending.as_str());
+
+    match &message.author {
+        Some(author) => format!("{} - {}: {}", date, author, body),
+        None => format!("{} - {}", date, body),
+    }
+}
+
+/// Formats messages as a chat export, one message per line, each line
+/// terminated by the line ending of the format.
+pub fn format_messages(messages: &[Message], format: &MessageFormat) -> String {
+    let mut output = String::new();
+    for message in messages {
+        output.push_str(&format_message(message, format));
+        output.push_str(format.line_ending.as_str());
+    }
+    output
+}
+
+/// Escapes the characters with a special meaning in HTML.
+fn html_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Escapes the characters with a special meaning in Markdown, with a
+/// backslash.
+///
+/// If `line_start` is `true` the text starts a line, and the markers of
+/// headings, lists and thematic breaks at its start (e.g. `# `, `- `, `1. `)
+/// are escaped too.
+fn markdown_escape(s: &str, line_start: bool) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    let start = s.len() - s.trim_start().len();
+    let digits = s[start..].len() - s[start..].trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    for (idx, c) in s.char_indices() {
+        let block_marker = line_start
+            && ((idx == start && matches!(c, '#' | '-' | '+' | '=')) || (digits > 0 && idx == start + digits && matches!(c, '.' | ')')));
+        if block_marker || matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '|' | '~') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Formats one line of a system message for Markdown: escaped and italicized,
+/// or empty for blank lines, which can't be italicized.
+fn markdown_system_line(line: &str) -> String {
+    let line = line.trim();
+    if line.is_empty() {
+        return String::new();
+    }
+    format!("*{}*", markdown_escape(line, false))
+}
+
+/// Generates a self-contained HTML page showing the conversation, with `title`
+/// as the page title and heading.
+///
+/// Each message is a `<div class="message user">` (or `system`) holding its
+/// author, timestamp and HTML-escaped content, with `<br>` between the lines
+/// of multiline messages.
+pub fn messages_to_html(messages: &[Message], title: &str) -> String {
+    let title = html_escape(title);
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>\n<style>\n\
+         body {{ font-family: sans-serif; max-width: 50em; margin: auto; }}\n\
+         .message {{ margin: 0.5em 0; padding: 0.5em; border-radius: 0.5em; background: #f0f0f0; }}\n\
+         .message.system {{ background: none; color: #666; text-align: center; font-style: italic; }}\n\
+         .author {{ font-weight: bold; }}\n\
+         .date {{ color: #888; font-size: 0.8em; }}\n\
+         </style>\n</head>\n<body>\n<h1>{}</h1>\n",
+        title, title
+    );
+
+    for message in messages {
+        let kind = if message.is_system() { "system" } else { "user" };
+        let content = message
+            .message
+            .split('\n')
+            .map(|line| html_escape(line.strip_suffix('\r').unwrap_or(line)))
+            .collect::<Vec<_>>()
+            .join("<br>");
+
+        html.push_str(&format!("<div class=\"message {}\">\n", kind));
+        if let Some(author) = &message.author {
+            html.push_str(&format!("<span class=\"author\">{}</span>\n", html_escape(author)));
+        }
+        html.push_str(&format!("<span class=\"date\">{}</span>\n", message.date.format("%d/%m/%Y %H:%M")));
+        html.push_str(&format!("<div class=\"content\">{}</div>\n</div>\n", content));
+    }
+
+    html.push_str("</body>\n</html>\n");
+    html
+}
+
+/// Formats messages as Markdown, under a `## YYYY-MM-DD` heading for each
+/// (UTC) day.
+///
+/// User messages are written as `**Author** (HH:MM): message` and each line of
+/// system messages is italicized. The continuation lines of multiline messages
+/// are indented with two spaces, and messages are separated by blank lines so
+/// each one is its own paragraph. Markdown syntax in authors and texts, such
+/// as `*`, `_` or a leading `#` or `-`, is escaped with backslashes.
+pub fn messages_to_markdown(messages: &[Message]) -> String {
+    let mut markdown = String::new();
+
+    for (day, day_messages) in group_messages_by_date(messages, DateGranularity::Day) {
+        if !markdown.is_empty() {
+            markdown.push('\n');
+        }
+        markdown.push_str(&format!("## {}\n", day.format("%Y-%m-%d")));
+
+        for message in day_messages {
+            let mut lines = message.message.split('\n').map(|line| line.strip_suffix('\r').unwrap_or(line));
+            let first_line = lines.next().unwrap_or("");
+            markdown.push('\n');
+            match &message.author {
+                Some(author) => markdown.push_str(&format!(
+                    "**{}** ({}): {}",
+                    markdown_escape(author, false),
+                    message.date.format("%H:%M"),
+                    markdown_escape(first_line, false)
+                )),
+                None => markdown.push_str(&markdown_system_line(first_line)),
+            }
+            markdown.push('\n');
+            for line in lines {
+                let line = match message.author {
+                    Some(_) => markdown_escape(line, true),
+                    None => markdown_system_line(line),
+                };
+                markdown.push_str(&format!("  {}\n", line));
+            }
+        }
+    }
+
+    markdown
+}
+
+/// The maximum number of characters of a message kept in the summary of its
+/// calendar event.
+#[cfg(feature = "ical")]
+const ICAL_SUMMARY_LENGTH: usize = 60;
+
+/// Escapes the characters with a special meaning in iCalendar text values.
+#[cfg(feature = "ical")]
+fn ical_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | ';' | ',' => {
+                escaped.push('\\');
+                escaped.push(c);
+            }
+            '\n' => escaped.push_str("\\n"),
+            '\r' => {}
+            _ => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Writes an iCalendar content line, folding it so that no line is longer than
+/// 75 bytes.
+#[cfg(feature = "ical")]
+fn write_ical_line<W: std::io::Write>(w: &mut W, line: &str) -> std::io::Result<()> {
+    let mut length = 0;
+    for c in line.chars() {
+        if length + c.len_utf8() > 75 {
+            w.write_all(b"\r\n ")?;
+            length = 1;
+        }
+        let mut buf = [0; 4];
+        w.write_all(c.encode_utf8(&mut buf).as_bytes())?;
+        length += c.len_utf8();
+    }
+    w.write_all(b"\r\n")
+}
+
+/// Writes the messages as an iCalendar (RFC 5545) calendar, with one event per
+/// message, e.g. to visualize the activity of a chat in a calendar app.
+///
+/// Each event starts at the date of its message and is summarized by the
+/// author and the first characters of the message.
+#[cfg(feature = "ical")]
+pub fn write_ical<W: std::io::Write>(messages: &[Message], mut w: W) -> std::io::Result<()> {
+    write_ical_line(&mut w, "BEGIN:VCALENDAR")?;
+    write_ical_line(&mut w, "VERSION:2.0")?;
+    write_ical_line(&mut w, "PRODID:-//wc-parser//EN")?;
+
+    for (idx, message) in messages.iter().enumerate() {
+        let date = message.date.format("%Y%m%dT%H%M%SZ");
+        let mut text: String = message.message.chars().take(ICAL_SUMMARY_LENGTH).collect();
+        if text.len() < message.message.len() {
+            text.push('…');
+        }
+        let summary = match &message.author {
+            Some(author) => format!("{}: {}", author, text),
+            None => text,
+        };
+
+        write_ical_line(&mut w, "BEGIN:VEVENT")?;
+        write_ical_line(&mut w, &format!("UID:{}-{}@wc-parser", message.date.timestamp(), idx))?;
+        write_ical_line(&mut w, &format!("DTSTAMP:{}", date))?;
+        write_ical_line(&mut w, &format!("DTSTART:{}", date))?;
+        write_ical_line(&mut w, &format!("SUMMARY:{}", ical_escape(&summary)))?;
+        write_ical_line(&mut w, "END:VEVENT")?;
+    }
+
+    write_ical_line(&mut w, "END:VCALENDAR")
+}
+
+/// Quotes a CSV field if it contains a separator, a quote or a line break.
+#[cfg(feature = "csv")]
+fn csv_field(field: &str) -> std::borrow::Cow<'_, str> {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\"")).into()
+    } else {
+        field.into()
+    }
+}
+
+/// Writes the messages as CSV, one row per message after a header row.
+///
+/// The columns are `timestamp` (RFC 3339), `author` (empty for system
+/// messages), `message`, `attachment_filename` (empty without an attachment)
+/// and `is_system` (`true` or `false`). Fields are quoted as described in
+/// RFC 4180 when needed, and rows end with `\n`.
+#[cfg(feature = "csv")]
+pub fn messages_to_csv<W: std::io::Write>(messages: &[Message], mut writer: W) -> std::io::Result<()> {
+    writer.write_all(b"timestamp,author,message,attachment_filename,is_system\n")?;
+    for message in messages {
+        let attachment = message.attachment.as_ref().map(|a| a.file_name.as_str()).unwrap_or("");
+        writeln!(
+            writer,
+            "{},{},{},{},{}",
+            message.date.to_rfc3339(),
+            csv_field(message.author.as_deref().unwrap_or("")),
+            csv_field(&message.message),
+            csv_field(attachment),
+            message.is_system()
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Utc};
+
+    fn message(author: Option<&str>, text: &str) -> Message {
+        Message {
+            date: Utc.with_ymd_and_hms(2021, 6, 23, 13, 5, 0).unwrap(),
+            author: author.map(|a| a.to_string()),
+            message: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_format_message() {
+        let format = MessageFormat::default();
+
+        assert_eq!(format_message(&message(Some("Luke"), "Hey!"), &format), "23/06/2021, 13:05 - Luke: Hey!");
+        assert_eq!(format_message(&message(None, "You created group \"Test\""), &format), "23/06/2021, 13:05 - You created group \"Test\"");
+    }
+
+    #[test]
+    fn test_format_messages_crlf() {
+        let messages = vec![message(Some("Luke"), "one\ntwo"), message(None, "system")];
+        let format = MessageFormat {
+            line_ending: LineEnding::CrLf,
+            ..Default::default()
+        };
+
+        assert_eq!(
+            format_messages(&messages, &format).as_bytes(),
+            b"23/06/2021, 13:05 - Luke: one\r\ntwo\r\n23/06/2021, 13:05 - system\r\n"
+        );
+        assert_eq!(
+            format_messages(&messages, &MessageFormat::default()).as_bytes(),
+            b"23/06/2021, 13:05 - Luke: one\ntwo\n23/06/2021, 13:05 - system\n"
+        );
+    }
+
+    #[test]
+    fn test_messages_to_markdown_escaping() {
+        let messages = vec![
+            message(Some("*Luke*"), "**not bold** and _not italic_\n# not a heading\n- not a list\n2. not a list either"),
+            message(None, ""),
+            message(None, "Luke added Leia\n- and Han"),
+        ];
+        let markdown = messages_to_markdown(&messages);
+
+        assert_eq!(
+            markdown,
+            "## 2021-06-23\n\n\
+             **\\*Luke\\*** (13:05): \\*\\*not bold\\*\\* and \\_not italic\\_\n  \\# not a heading\n  \\- not a list\n  2\\. not a list either\n\
+             \n\n\
+             \n*Luke added Leia*\n  *- and Han*\n"
+        );
+        assert!(!markdown.contains("\n**\n"));
+        assert_eq!(markdown_escape("3 - 2 = 1 <3 [x](y) `code` | ~", true), "3 - 2 = 1 \\<3 \\[x\\](y) \\`code\\` \\| \\~");
+        assert_eq!(markdown_escape("# tag", false), "# tag");
+    }
+
+    #[test]
+    fn test_messages_to_html() {
+        let messages = vec![
+            message(Some("Luke"), "<script>alert(1)</script>\nsecond line"),
+            message(None, "You created group \"Test\""),
+        ];
+        let html = messages_to_html(&messages, "Rebels & co");
+
+        assert!(html.contains("<title>Rebels &amp; co</title>"));
+        assert!(html.contains("<h1>Rebels &amp; co</h1>"));
+        assert!(!html.contains("<script>"));
+        assert!(html.contains("&lt;script&gt;alert(1)&lt;/script&gt;<br>second line"));
+        assert_eq!(html.matches("<div class=\"message user\">").count(), 1);
+        assert!(html.contains("<div class=\"message system\">"));
+        assert!(html.contains("You created group &quot;Test&quot;"));
+        assert!(html.contains("<span class=\"date\">23/06/2021 13:05</span>"));
+    }
+
+    #[cfg(feature = "ical")]
+    #[test]
+    fn test_write_ical() {
+        let messages = vec![
+            message(Some("Luke"), "Hey, how are you?\nLong time no see"),
+            message(None, "You created group \"Test\""),
+            message(Some("Leia"), &"a".repeat(100)),
+        ];
+        let mut output = Vec::new();
+        write_ical(&messages, &mut output).unwrap();
+        let output = String::from_utf8(output).unwrap();
+
+        assert!(output.starts_with("BEGIN:VCALENDAR\r\n"));
+        assert!(output.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(output.matches("BEGIN:VEVENT\r\n").count(), 3);
+        assert!(output.contains("\r\nDTSTART:20210623T130500Z\r\n"));
+        assert!(output.contains("\r\nSUMMARY:Luke: Hey\\, how are you?\\nLong time no see\r\n"));
+        assert!(output.split("\r\n").all(|line| line.len() <= 75));
+    }
+
+    /// Minimal RFC 4180 reader, to check the output of `messages_to_csv`.
+    #[cfg(feature = "csv")]
+    fn read_csv(input: &str) -> Vec<Vec<String>> {
+        let mut rows = vec![];
+        let mut row = vec![];
+        let mut field = String::new();
+        let mut quoted = false;
+        let mut chars = input.chars().peekable();
+        while let Some(c) = chars.next() {
+            match (quoted, c) {
+                (true, '"') if chars.peek() == Some(&'"') => {
+                    field.push('"');
+                    chars.next();
+                }
+                (true, '"') => quoted = false,
+                (true, _) => field.push(c),
+                (false, '"') => quoted = true,
+                (false, ',') => row.push(std::mem::take(&mut field)),
+                (false, '\n') => {
+                    row.push(std::mem::take(&mut field));
+                    rows.push(std::mem::take(&mut row));
+                }
+                (false, _) => field.push(c),
+            }
+        }
+        rows
+    }
+
+    #[cfg(feature = "csv")]
+    #[test]
+    fn test_messages_to_csv() {
+        let mut with_attachment = message(Some("Leia"), "IMG-1.jpg (file attached)");
+        with_attachment.attachment = Some(crate::models::Attachment {
+            file_name: "IMG-1.jpg".to_string(),
+            ..Default::default()
+        });
+        let messages = vec![
+            message(Some("Luke"), "Hey, \"Leia\"\nhow are you?"),
+            message(None, "You created group \"Test\""),
+            with_attachment,
+        ];
+        let mut output = std::io::Cursor::new(Vec::new());
+        messages_to_csv(&messages, &mut output).unwrap();
+        let rows = read_csv(&String::from_utf8(output.into_inner()).unwrap());
+
+        assert_eq!(rows.len(), 4);
+        assert_eq!(rows[0], vec!["timestamp", "author", "message", "attachment_filename", "is_system"]);
+        assert_eq!(rows[1], vec!["2021-06-23T13:05:00+00:00", "Luke", "Hey, \"Leia\"\nhow are you?", "", "false"]);
+        assert_eq!(rows[2], vec!["2021-06-23T13:05:00+00:00", "", "You created group \"Test\"", "", "true"]);
+        assert_eq!(rows[3][3], "IMG-1.jpg");
+    }
+}
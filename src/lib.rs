@@ -2,38 +2,343 @@
 pub mod datetime;
 pub mod parser;
 pub mod models;
+pub mod analysis;
+pub mod error;
+pub mod export;
+pub mod system;
+#[cfg(feature = "encoding")]
+pub mod encoding;
+#[cfg(any(test, feature = "test-utils"))]
+pub mod test_utils;
 
-use crate::parser::{parse_messages};
-use crate::models::{Message, ParseStringOptions};
+pub use crate::parser::{is_message_start, is_system_message_start};
+pub use crate::system::{parse_metadata, ChatMetadata};
 
+use crate::parser::{Lines, collect_authors, count_messages, find_date_range, parse_messages, parse_messages_epoch, parse_messages_since, parse_messages_with_warnings};
+use crate::error::{ParseError, ParseLineError, ParseWarning, SizeLimit};
+use crate::models::{ErrorPolicy, Message, ParseStats, ParseStringOptions, ParsedChat, RawMessage, SystemMessageKind};
+
+use chrono::{DateTime, Utc};
 use std::fs::File;
 use std::io::Result as IoResult;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "mmap")]
 use memmap2::Mmap;
 
-pub fn parse_string(s: &str, options: Option<ParseStringOptions>) -> Result<Vec<Message>, String> {
-    let lines: Vec<&str> = s.split('\n').collect();
-    let opts = options.unwrap_or_default();
-    let debug = opts.debug;
+/// Fails with `ParseError::TooLarge` if `len` bytes exceed the `max_bytes`
+/// limit set in the options.
+fn check_max_bytes(len: usize, options: &ParseStringOptions) -> Result<(), ParseError> {
+    match options.max_bytes {
+        Some(max) if len > max => Err(ParseError::TooLarge(SizeLimit::Bytes(max))),
+        _ => Ok(()),
+    }
+}
+
+/// Returns an iterator over the lines of the input, enforcing the size limits
+/// set in the options.
+///
+/// The lines are only counted when `max_lines` is set, with a fast scan for
+/// line breaks.
+fn split_lines<'a>(s: &'a str, options: &ParseStringOptions) -> Result<Lines<'a>, ParseError> {
+    check_max_bytes(s.len(), options)?;
+    match options.max_lines {
+        Some(max) if memchr::memchr_iter(b'\n', s.as_bytes()).count() + 1 > max => {
+            Err(ParseError::TooLarge(SizeLimit::Lines(max)))
+        }
+        _ => Ok(Lines::new(s)),
+    }
+}
+
+/// Groups the lines of the input into raw messages, reporting the progress to
+/// the `progress_callback` set in the options.
+fn aggregate_with_progress<'a>(s: &str, lines: Lines<'a>, options: &ParseStringOptions) -> (Vec<RawMessage>, Vec<ParseLineError>) {
+    let Some(callback) = &options.progress_callback else {
+        return parser::aggregate_lines_with_options(lines, options);
+    };
+
+    let total = memchr::memchr_iter(b'\n', s.as_bytes()).count() + 1;
+    let interval = options.progress_report_interval.max(1);
+    let lines = lines.enumerate().map(|(idx, line)| {
+        let processed = idx + 1;
+        if processed.is_multiple_of(interval) && processed < total {
+            callback(processed, total);
+        }
+        line
+    });
+    let result = parser::aggregate_lines_with_options(lines, options);
+    callback(total, total);
+    result
+}
+
+pub fn parse_string(s: &str, options: Option<ParseStringOptions>) -> Result<Vec<Message>, ParseError> {
+    parse_str_with_options(s, &options.unwrap_or_default())
+}
+
+/// Same as [`parse_string`], borrowing the options so they can be reused.
+fn parse_str_with_options(s: &str, opts: &ParseStringOptions) -> Result<Vec<Message>, ParseError> {
+    let lines = split_lines(s, opts)?;
+    let log = parser::DebugLog::new(opts);
     
-    if debug {
-        println!("🔍 DEBUG: parse_string called with {} characters", s.len());
-        println!("🔍 DEBUG: Split into {} lines", lines.len());
-        println!("🔍 DEBUG: Options: {:?}", opts);
-        println!("🔍 DEBUG: =====================================");
+    if log.enabled() {
+        log.write(&format!("🔍 DEBUG: parse_string called with {} characters", s.len()));
+        log.write(&format!("🔍 DEBUG: Options: {:?}", opts));
+        log.write("🔍 DEBUG: =====================================");
     }
     
-    Ok(parse_messages(&parser::make_array_of_messages_with_debug(&lines, debug), &opts))
+    parse_messages(&aggregate_with_progress(s, lines, opts).0, opts)
+}
+
+/// Same as [`parse_string`] but packages the messages into a [`ParsedChat`],
+/// with the title of the chat taken from the first group creation notice
+/// (English exports only).
+pub fn parse_chat(s: &str, options: Option<ParseStringOptions>) -> Result<ParsedChat, ParseError> {
+    let messages = parse_string(s, options)?;
+    let title = messages.iter().find_map(|m| match &m.system_kind {
+        Some(SystemMessageKind::GroupCreated { name }) => Some(name.clone()),
+        _ => None,
+    });
+
+    Ok(ParsedChat { title, messages })
+}
+
+/// Same as [`parse_string`] but takes raw bytes, e.g. from a network buffer or
+/// an embedded resource.
+///
+/// Fails with `ParseError::Utf8Error` if the bytes are not valid UTF-8.
+pub fn parse_bytes(bytes: &[u8], options: Option<ParseStringOptions>) -> Result<Vec<Message>, ParseError> {
+    let text = std::str::from_utf8(bytes)?;
+    parse_string(text, options)
+}
+
+/// Same as [`parse_string`] but also returns the lines that could not be
+/// parsed, instead of silently discarding them.
+///
+/// Size limits are not enforced, every line of the input is processed, and
+/// messages that can't be parsed are always skipped.
+pub fn parse_string_lenient(s: &str, options: ParseStringOptions) -> (Vec<Message>, Vec<ParseLineError>) {
+    let (raw_messages, errors) = aggregate_with_progress(s, Lines::new(s), &options);
+    let options = ParseStringOptions {
+        on_parse_error: ErrorPolicy::Skip,
+        ..options
+    };
+
+    (parse_messages(&raw_messages, &options).unwrap_or_default(), errors)
+}
+
+/// Same as [`parse_string_lenient`] but bundles every non-fatal problem into a
+/// single list of warnings: dropped lines, skipped messages, and a date order
+/// that had to be assumed.
+pub fn parse_string_verbose(s: &str, options: ParseStringOptions) -> (Vec<Message>, Vec<ParseWarning>) {
+    let (raw_messages, errors) = aggregate_with_progress(s, Lines::new(s), &options);
+    let (messages, warnings) = parse_messages_with_warnings(&raw_messages, &options);
+
+    let mut all_warnings: Vec<ParseWarning> = errors.into_iter().map(ParseWarning::DroppedLine).collect();
+    all_warnings.extend(warnings);
+    (messages, all_warnings)
+}
+
+/// Same as [`parse_string`] but also returns counters about the lines of the
+/// input: how many started a message, continued one, or were discarded.
+pub fn parse_string_with_stats(s: &str, options: Option<ParseStringOptions>) -> Result<(Vec<Message>, ParseStats), ParseError> {
+    let opts = options.unwrap_or_default();
+    let lines = split_lines(s, &opts)?;
+    let (raw_messages, _, stats) = parser::aggregate_lines_with_stats(lines, &opts);
+
+    Ok((parse_messages(&raw_messages, &opts)?, stats))
+}
+
+/// Same as [`parse_string`] but returns `(timestamp, author, message)` tuples
+/// where `timestamp` is the number of seconds since the Unix epoch (UTC).
+///
+/// Useful when storing large amounts of messages, as it skips building the
+/// `Message` structs (and attachment parsing) entirely.
+pub fn parse_epoch(s: &str, options: Option<ParseStringOptions>) -> Result<Vec<(i64, Option<String>, String)>, ParseError> {
+    let opts = options.unwrap_or_default();
+    let lines = split_lines(s, &opts)?;
+
+    parse_messages_epoch(&aggregate_with_progress(s, lines, &opts).0, &opts)
+}
+
+/// Same as [`parse_string`] but only returns the messages sent strictly after
+/// `since`, for incremental imports resuming from the last seen message.
+///
+/// The author and body of earlier messages are never extracted, which makes
+/// this cheaper than filtering the output of [`parse_string`].
+pub fn parse_since(s: &str, options: Option<ParseStringOptions>, since: DateTime<Utc>) -> Result<Vec<Message>, ParseError> {
+    let opts = options.unwrap_or_default();
+    let lines = split_lines(s, &opts)?;
+
+    parse_messages_since(&aggregate_with_progress(s, lines, &opts).0, &opts, since)
+}
+
+/// Counts the messages in a chat export without parsing them.
+///
+/// Much cheaper than `parse_string(s, None)?.len()` since no dates, authors or
+/// bodies are extracted, but gives the same result.
+pub fn parse_string_count(s: &str) -> usize {
+    count_messages(Lines::new(s))
+}
+
+/// Memory-mapped counterpart of [`parse_string_count`], see [`parse_file`].
+pub fn parse_file_count<P: AsRef<Path>>(path: P) -> IoResult<usize> {
+    with_mapped_file(path, parse_string_count)
+}
+
+/// Returns the sorted, deduplicated names of everyone who sent a message in a
+/// chat export, without parsing the messages.
+///
+/// Gives the same names as collecting the authors of `parse_string(s, None)`,
+/// but skips all the date and time processing.
+pub fn parse_string_authors(s: &str) -> Vec<String> {
+    collect_authors(Lines::new(s))
+}
+
+/// Memory-mapped counterpart of [`parse_string_authors`], see [`parse_file`].
+pub fn parse_file_authors<P: AsRef<Path>>(path: P) -> IoResult<Vec<String>> {
+    with_mapped_file(path, parse_string_authors)
+}
+
+/// Returns the dates of the earliest and latest messages in a chat export,
+/// without building the messages.
+///
+/// Only the date and time at the start of each message are processed. Output
+/// is `None` if the export contains no messages.
+pub fn parse_string_date_range(s: &str, options: Option<ParseStringOptions>) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    find_date_range(Lines::new(s), &options.unwrap_or_default())
+}
+
+/// Memory-mapped counterpart of [`parse_string_date_range`], see [`parse_file`].
+pub fn parse_file_date_range<P: AsRef<Path>>(path: P, options: Option<ParseStringOptions>) -> IoResult<Option<(DateTime<Utc>, DateTime<Utc>)>> {
+    with_mapped_file(path, |text| parse_string_date_range(text, options))
+}
+
+/// The contents of a file: memory-mapped with the `mmap` feature, otherwise
+/// read into memory.
+#[cfg(feature = "mmap")]
+type FileContents = Mmap;
+#[cfg(not(feature = "mmap"))]
+type FileContents = Vec<u8>;
+
+/// Memory-maps a file, or reads it whole without the `mmap` feature.
+fn map_file(file: &File) -> IoResult<FileContents> {
+    #[cfg(feature = "mmap")]
+    {
+        // SAFETY: the map is only read, and exports are not expected to be
+        // modified while they are parsed.
+        unsafe { Mmap::map(file) }
+    }
+    #[cfg(not(feature = "mmap"))]
+    {
+        use std::io::Read;
+        let mut contents = Vec::new();
+        let mut reader = file;
+        reader.read_to_end(&mut contents)?;
+        Ok(contents)
+    }
+}
+
+/// Returns the size of a file in bytes, from its metadata, so `max_bytes` can
+/// be checked before the file is mapped or read.
+fn file_len(file: &File) -> IoResult<usize> {
+    Ok(usize::try_from(file.metadata()?.len()).unwrap_or(usize::MAX))
+}
+
+/// Memory-maps a file and runs `f` on its contents, failing with
+/// `ErrorKind::InvalidData` if they are not valid UTF-8.
+fn with_mapped_file<P: AsRef<Path>, R>(path: P, f: impl FnOnce(&str) -> R) -> IoResult<R> {
+    let mmap = map_file(&File::open(path)?)?;
+    let text = std::str::from_utf8(&mmap).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    Ok(f(text))
 }
 
 /// Convenience helper that memory-maps a chat export file and parses it without
 /// copying its contents into an intermediate `String`.
 ///
 /// This keeps peak memory low (the OS brings pages in on demand) and can be
-/// noticeably faster on very large exports.
+/// noticeably faster on very large exports. Without the default `mmap`
+/// feature the whole file is read into memory first instead, so peak memory
+/// grows with the size of the file.
+///
+/// Parsing errors are returned as `std::io::Error`s wrapping the original
+/// [`ParseError`]. Files that are not valid UTF-8 fail with
+/// `ErrorKind::InvalidData`, and the offset of the first invalid byte in the
+/// error message.
 pub fn parse_file<P: AsRef<Path>>(path: P, options: Option<ParseStringOptions>) -> IoResult<Vec<Message>> {
     let file = File::open(path)?;
-    let mmap = unsafe { Mmap::map(&file)? };
-    let text: &str = std::str::from_utf8(&mmap).expect("Chat file is not valid UTF-8");
-    parse_string(text, options).map_err(|e| std::io::Error::new(std::io::ErrorKind::Other, e))
+    if let Some(opts) = &options {
+        check_max_bytes(file_len(&file)?, opts).map_err(std::io::Error::other)?;
+    }
+    let mmap = map_file(&file)?;
+    let text = std::str::from_utf8(&mmap).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    parse_string(text, options).map_err(std::io::Error::other)
+}
+
+/// Same as [`parse_file`] but also accepts files that are not encoded in
+/// UTF-8, such as the Latin-1 or Windows-1252 files of some older Android
+/// exports.
+///
+/// UTF-8 is tried first, then UTF-16 if the file starts with a byte order
+/// mark, and Windows-1252 as a last resort; see [`encoding::decode`]. UTF-8
+/// files are parsed without copying.
+#[cfg(feature = "encoding")]
+pub fn parse_file_with_encoding<P: AsRef<Path>>(path: P, options: Option<ParseStringOptions>) -> IoResult<Vec<Message>> {
+    let file = File::open(path)?;
+    let opts = options.unwrap_or_default();
+    check_max_bytes(file_len(&file)?, &opts).map_err(std::io::Error::other)?;
+    let mmap = map_file(&file)?;
+    parse_str_with_options(&encoding::decode(&mmap), &opts).map_err(std::io::Error::other)
+}
+
+/// Memory-maps and parses a single file for [`load_and_merge`].
+fn load_file(path: &Path, options: &ParseStringOptions) -> Result<Vec<Message>, ParseError> {
+    let file = File::open(path)?;
+    check_max_bytes(file_len(&file)?, options)?;
+    let mmap = map_file(&file)?;
+    parse_str_with_options(std::str::from_utf8(&mmap)?, options)
+}
+
+/// Parses several chat exports, e.g. monthly exports of the same chat, and
+/// merges their messages.
+///
+/// The files are memory-mapped and parsed in parallel. The merged messages
+/// are sorted by date, messages sent at the same time keeping the order of
+/// `paths` and of their file, see [`analysis::sort_messages`]. Messages with
+/// the same date, author and text, such as the ones in the overlap of two
+/// exports, are only kept once; see [`analysis::dedup_messages`].
+///
+/// A file that can't be read or parsed doesn't stop the others from being
+/// loaded: its error is returned alongside the messages, with its path.
+pub fn load_and_merge(paths: &[PathBuf], options: Option<ParseStringOptions>) -> (Vec<Message>, Vec<(PathBuf, ParseError)>) {
+    let opts = options.unwrap_or_default();
+    #[cfg(feature = "parallel")]
+    let results: Vec<_> = {
+        use rayon::prelude::*;
+        paths.par_iter().map(|path| load_file(path, &opts)).collect()
+    };
+    #[cfg(not(feature = "parallel"))]
+    let results: Vec<_> = paths.iter().map(|path| load_file(path, &opts)).collect();
+
+    let mut messages = Vec::new();
+    let mut errors = Vec::new();
+    for (path, result) in paths.iter().zip(results) {
+        match result {
+            Ok(file_messages) => messages.extend(file_messages),
+            Err(err) => errors.push((path.clone(), err)),
+        }
+    }
+
+    analysis::sort_messages(&mut messages);
+    analysis::dedup_messages(&mut messages);
+    (messages, errors)
+}
+
+/// Same as [`load_and_merge`], failing with the error of the first file, in
+/// the order of `paths`, that can't be read or parsed.
+pub fn parse_files<P: AsRef<Path>>(paths: &[P], options: Option<ParseStringOptions>) -> Result<Vec<Message>, ParseError> {
+    let paths: Vec<PathBuf> = paths.iter().map(|path| path.as_ref().to_path_buf()).collect();
+    let (messages, errors) = load_and_merge(&paths, options);
+    match errors.into_iter().next() {
+        Some((_, err)) => Err(err),
+        None => Ok(messages),
+    }
 }
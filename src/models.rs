@@ -1,18 +1,155 @@
+use crate::datetime::{DateTimeParser, DEFAULT_TWO_DIGIT_YEAR_CUTOFF};
 use chrono::{DateTime, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
+use std::collections::HashMap;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+#[cfg(feature = "parallel")]
+use std::sync::Arc;
 
-#[derive(Debug, PartialEq)]
+lazy_static! {
+    // Newer exports wrap the mentioned name in isolate marks (U+2068 ... U+2069),
+    // which lets names with spaces through. Older ones only have a bare token.
+    static ref REGEX_MENTION: Regex = Regex::new(r"(?:^|[^\w@])@(?:\u{2068}([^\u{2069}]+)\u{2069}|(\+?\w[\w.-]*))").unwrap();
+}
+
+#[derive(Debug, PartialEq, Default)]
 pub struct RawMessage {
     pub system: bool,
     pub msg: String,
+    /// The number of the first line of the message in the input, starting
+    /// from `1`, or `0` if unknown (e.g. for raw messages built by hand).
+    pub line_start: usize,
+    /// The number of the last line of the message in the input, the same as
+    /// `line_start` unless the message spans several lines.
+    pub line_end: usize,
+}
+
+/// Counters about the lines of an export, collected while grouping them into
+/// messages.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ParseStats {
+    /// The number of lines in the input.
+    pub total_lines: usize,
+    /// The number of lines starting a user message.
+    pub message_starts: usize,
+    /// The number of lines appended to the previous message (multiline
+    /// messages).
+    pub continuation_lines: usize,
+    /// The number of lines starting a system message.
+    pub system_message_starts: usize,
+    /// The number of lines that are not part of any message, see
+    /// `ParseLineErrorReason::NoPrecedingMessage`.
+    pub discarded_lines: usize,
+}
+
+/// The kind of a media file shared in a chat.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum MediaKind {
+    Image,
+    Video,
+    Audio,
+    Sticker,
+    Gif,
+    Document,
 }
 
-#[derive(Debug, PartialEq)]
+/// The kind of a system message, recognized from the English notices.
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum SystemMessageKind {
+    /// The notice that messages are end-to-end encrypted.
+    EndToEndEncryption,
+    /// A participant created the group. Carries the name the group was created
+    /// with, without the surrounding quotes.
+    GroupCreated { name: String },
+    /// A participant renamed the group.
+    GroupNameChanged { new_name: String },
+    /// A participant was added to the group. Carries the added participant(s).
+    ParticipantAdded(String),
+    /// A participant was removed from the group. Carries the removed
+    /// participant.
+    ParticipantRemoved(String),
+    /// A participant left the group.
+    ParticipantLeft,
+    /// A participant became an admin of the group. Carries the new admin.
+    AdminChanged(String),
+    /// A participant changed their phone number. Carries the new number when
+    /// the notice includes it (e.g. `+41 79 111 changed to +41 79 222`).
+    NumberChanged { new_number: Option<String> },
+    /// Disappearing messages were turned on or off, or their timer changed.
+    MessageTimerChanged,
+    /// Any other system message.
+    Unknown,
+}
+
+#[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
 pub struct Attachment {
     /// The filename of the attachment, including the extension.
     pub file_name: String,
+    /// The size of the file in bytes, for exports noting it after the filename
+    /// (e.g. `IMG-1.jpg (2.3 MB) (file attached)`). Units are powers of 1024.
+    pub size: Option<u64>,
+    /// The kind of media, for the placeholders of exports made without media
+    /// (e.g. `sticker omitted`). `None` for attached files.
+    pub kind: Option<MediaKind>,
+}
+
+impl Attachment {
+    /// Returns the extension of the file name, without the leading dot.
+    ///
+    /// Follows the rules of [`Path::extension`](std::path::Path::extension):
+    /// only the part after the last dot is returned (`archive.tar.gz` gives
+    /// `gz`), and dotfiles such as `.gitignore` have no extension. The returned
+    /// slice borrows from `file_name`.
+    pub fn extension(&self) -> Option<&str> {
+        std::path::Path::new(&self.file_name).extension().and_then(|ext| ext.to_str())
+    }
+
+    /// Returns the MIME type of the attachment, inferred from the extension of
+    /// the file name.
+    ///
+    /// Unknown or missing extensions give `application/octet-stream`.
+    pub fn mime_type(&self) -> &'static str {
+        match self.extension().map(str::to_lowercase).as_deref() {
+            Some("jpg") | Some("jpeg") => "image/jpeg",
+            Some("png") => "image/png",
+            Some("gif") => "image/gif",
+            Some("webp") => "image/webp",
+            Some("mp4") => "video/mp4",
+            Some("3gp") => "video/3gpp",
+            Some("mp3") => "audio/mpeg",
+            Some("ogg") | Some("opus") => "audio/ogg",
+            Some("m4a") => "audio/mp4",
+            Some("pdf") => "application/pdf",
+            Some("vcf") => "text/vcard",
+            Some("txt") => "text/plain",
+            _ => "application/octet-stream",
+        }
+    }
+
+    /// Returns the path of the attachment in `base_dir`, the directory an
+    /// export was extracted to.
+    ///
+    /// Only the plain components of the file name are kept: `..`, `.` and
+    /// leading `/` are dropped, so the path can't point outside of `base_dir`.
+    /// Subdirectories in the file name are kept.
+    pub fn full_path(&self, base_dir: &std::path::Path) -> std::path::PathBuf {
+        let mut path = base_dir.to_path_buf();
+        for component in std::path::Path::new(&self.file_name).components() {
+            if let std::path::Component::Normal(part) = component {
+                path.push(part);
+            }
+        }
+        path
+    }
 }
 
-#[derive(Debug, PartialEq)]
+/// A parsed message.
+///
+/// Messages are ordered by `date`, then `author` (system messages first), then
+/// `message`; the other fields only break the remaining ties.
+#[derive(Debug, PartialEq, Eq, Default)]
 pub struct Message {
     /// The date of the message.
     pub date: DateTime<Utc>,
@@ -23,24 +160,568 @@ pub struct Message {
     /// Available for messages containing attachments when setting the option
     /// `parse_attachments` to `true`.
     pub attachment: Option<Attachment>,
+    /// The index of the conversation session the message belongs to. Only set
+    /// after running `analysis::assign_session_ids` on the messages.
+    pub session_id: Option<usize>,
+    /// Whether the message was starred (bookmarked). Only detected when
+    /// `star_markers` is set in the options, the marker is then removed from
+    /// `message`.
+    pub starred: bool,
+    /// The kind of the message, for system messages. Always `None` for user
+    /// messages.
+    pub system_kind: Option<SystemMessageKind>,
+}
+
+/// A parsed chat: its messages, packaged with information about the chat as a
+/// whole.
+#[derive(Debug, PartialEq, Default)]
+pub struct ParsedChat {
+    /// The name of the group, taken from the notice of its creation. `None`
+    /// if the export doesn't contain that notice.
+    pub title: Option<String>,
+    /// The parsed messages.
+    pub messages: Vec<Message>,
+}
+
+/// Formats the message as a chat export line, `DD/MM/YYYY, HH:MM - Author:
+/// Message` or `DD/MM/YYYY, HH:MM - Message` for system messages.
+///
+/// See [`export::format_message`](crate::export::format_message) to choose the
+/// date format or line ending.
+impl fmt::Display for Message {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let date = self.date.format("%d/%m/%Y, %H:%M");
+        match &self.author {
+            Some(author) => write!(f, "{} - {}: {}", date, author, self.message),
+            None => write!(f, "{} - {}", date, self.message),
+        }
+    }
+}
+
+// Only the fields identifying a message are hashed, equal messages still have
+// equal hashes
+impl Hash for Message {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.date.timestamp().hash(state);
+        self.author.hash(state);
+        self.message.hash(state);
+    }
+}
+
+impl Ord for Message {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.date
+            .cmp(&other.date)
+            .then_with(|| self.author.cmp(&other.author))
+            .then_with(|| self.message.cmp(&other.message))
+            .then_with(|| self.attachment.cmp(&other.attachment))
+            .then_with(|| self.session_id.cmp(&other.session_id))
+            .then_with(|| self.starred.cmp(&other.starred))
+            .then_with(|| self.system_kind.cmp(&other.system_kind))
+    }
+}
+
+impl PartialOrd for Message {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Message {
+    /// Creates a message sent at `ts`, a number of seconds since the Unix
+    /// epoch, e.g. for synthetic messages in tests. `author` is `None` for
+    /// system messages.
+    ///
+    /// Timestamps outside of the range supported by `chrono` give the Unix
+    /// epoch.
+    pub fn from_timestamp_unix(ts: i64, author: Option<String>, message: String) -> Message {
+        Message {
+            date: DateTime::from_timestamp(ts, 0).unwrap_or_default(),
+            author,
+            message,
+            ..Default::default()
+        }
+    }
+
+    /// Returns the date of the message as a number of seconds since the Unix
+    /// epoch.
+    pub fn timestamp_unix(&self) -> i64 {
+        self.date.timestamp()
+    }
+
+    /// Returns the date of the message as a number of milliseconds since the
+    /// Unix epoch, as used by JavaScript dates.
+    pub fn timestamp_unix_millis(&self) -> i64 {
+        self.date.timestamp_millis()
+    }
+
+    /// Returns `true` for system messages, which have no author.
+    pub fn is_system(&self) -> bool {
+        self.author.is_none()
+    }
+
+    /// Returns `true` for messages sent by a participant.
+    pub fn is_user(&self) -> bool {
+        self.author.is_some()
+    }
+
+    /// Returns `true` for messages sharing a media file: messages with a parsed
+    /// attachment, and the `<Media omitted>` placeholders of exports made
+    /// without media.
+    pub fn is_media(&self) -> bool {
+        self.attachment.is_some() || crate::parser::strip_directional_marks(&self.message).trim() == "<Media omitted>"
+    }
+
+    /// Counts the words of the message, split on whitespace.
+    ///
+    /// Scripts that don't separate words with spaces, such as Chinese or
+    /// Japanese, are counted as one word per whitespace-separated run.
+    /// Punctuation surrounded by spaces counts as a word.
+    pub fn word_count(&self) -> usize {
+        self.message.split_whitespace().count()
+    }
+
+    /// Returns the participants mentioned in the message with `@Name` or
+    /// `@phonenumber`, without the leading `@`, in order of appearance.
+    pub fn mentions(&self) -> Vec<&str> {
+        REGEX_MENTION
+            .captures_iter(&self.message)
+            .filter_map(|caps| caps.get(1).or_else(|| caps.get(2)))
+            .map(|m| m.as_str().trim_end_matches(['.', '-']))
+            .collect()
+    }
+}
+
+/// What to do with messages that can't be parsed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Leave the message out of the result.
+    #[default]
+    Skip,
+    /// Stop parsing and return an error.
+    Fail,
+}
+
+/// A function receiving the debug output, see `ParseStringOptions::log_sink`.
+pub type LogSink = dyn Fn(&str) + Send + Sync;
+
+/// The order of the day and month in the dates of a chat export.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DateOrder {
+    /// Days come before months, e.g. `23/06/2018`.
+    #[default]
+    DayMonthYear,
+    /// Months come before days, e.g. `06/23/2018`.
+    MonthDayYear,
+}
+
+impl DateOrder {
+    /// Returns `true` if days come before months.
+    pub fn days_first(&self) -> bool {
+        *self == DateOrder::DayMonthYear
+    }
 }
 
-#[derive(Debug, Default)]
 pub struct ParseStringOptions {
     /// Specify if the dates in your log file start with a day (`true`) or a month
     /// (`false`).
     ///
     /// Manually specifying this may improve performance.
     pub days_first: Option<bool>,
+    /// The order of the date components used when `days_first` is not set and
+    /// it can't be detected, e.g. in very short chats or when every day and
+    /// month is `12` or lower.
+    ///
+    /// Defaults to `DateOrder::DayMonthYear`, the most common order.
+    pub preferred_order: DateOrder,
     /// Specify if attachments should be parsed.
     ///
     /// If set to `true`, messages containing attachments will include an
     /// `attachment` property.
     pub parse_attachments: bool,
+    /// Specify if attachment markers can appear anywhere in a message.
+    ///
+    /// By default only messages starting with the attachment are recognized.
+    /// If set to `true`, markers following a caption (e.g. `Look IMG-1.jpg (file
+    /// attached)`) are also detected, as long as the file name has no spaces.
+    /// Only used when `parse_attachments` is `true`.
+    pub attachments_anywhere: bool,
+    /// Markers identifying starred messages, e.g. `"⭐"`.
+    ///
+    /// A message starting or ending with one of the markers has `starred` set
+    /// to `true` and the marker removed from its body. Set the markers used by
+    /// the tool or language the export was made with. Empty by default, which
+    /// disables the detection.
+    pub star_markers: Vec<String>,
+    /// Leave out messages whose body is empty once cleaned up, e.g. bodies
+    /// made only of directional marks or of a star marker.
+    ///
+    /// Messages with a parsed attachment are always kept.
+    pub drop_empty: bool,
+    /// Author names containing `": "`, e.g. `"Company: Support"`.
+    ///
+    /// User messages are split into author and body at the first `": "`, so
+    /// `Company: Support: Hello` is sent by `Company` by default. A message
+    /// starting with one of these names followed by `": "` is split after the
+    /// name instead, preferring the longest name that matches. Empty by
+    /// default.
+    pub known_authors: Vec<String>,
     /// Enable debug output during parsing.
     ///
     /// If set to `true`, detailed information about the parsing process will be
-    /// printed to stdout, including regex matches, message processing steps, and
-    /// statistics.
+    /// printed to stdout, or sent to `log_sink` if set, including regex matches,
+    /// message processing steps, and statistics.
     pub debug: bool,
+    /// Receives the debug output, one message per call, instead of stdout.
+    ///
+    /// Only called when `debug` is `true`.
+    pub log_sink: Option<Box<LogSink>>,
+    /// Refuse inputs larger than this many bytes.
+    ///
+    /// If set, parsing fails early with `ParseError::TooLarge` instead of
+    /// processing the input. For files the check uses the size on disk, before
+    /// the file is mapped or read.
+    pub max_bytes: Option<usize>,
+    /// Refuse inputs with more than this many lines.
+    ///
+    /// If set, parsing fails early with `ParseError::TooLarge` instead of
+    /// processing the input.
+    pub max_lines: Option<usize>,
+    /// Specify what happens with messages that can't be parsed.
+    ///
+    /// Defaults to `ErrorPolicy::Skip`, which leaves them out of the result.
+    /// With `ErrorPolicy::Fail` parsing stops with an error instead.
+    pub on_parse_error: ErrorPolicy,
+    /// The Rayon thread pool used to parse messages in parallel.
+    ///
+    /// If `None`, the global Rayon pool is used.
+    #[cfg(feature = "parallel")]
+    pub thread_pool: Option<Arc<rayon::ThreadPool>>,
+    /// The smallest number of messages parsed in parallel. Smaller chats are
+    /// parsed on the calling thread, which is faster for a handful of messages.
+    ///
+    /// Defaults to `None`, which uses `parser::PARALLEL_THRESHOLD`.
+    #[cfg(feature = "parallel")]
+    pub parallel_threshold: Option<usize>,
+    /// Called with `(lines_processed, total_lines)` while the lines of the
+    /// input are grouped into messages, to report the progress of long parses.
+    ///
+    /// Called every `progress_report_interval` lines, and a last time with
+    /// `(total_lines, total_lines)` once all lines are processed.
+    pub progress_callback: Option<Box<dyn Fn(usize, usize) + Send + Sync>>,
+    /// The number of lines between two calls of `progress_callback`.
+    ///
+    /// Defaults to `1000`.
+    pub progress_report_interval: usize,
+    /// A custom parser for the dates at the start of the messages.
+    ///
+    /// If set, it's used instead of the built-in date detection and
+    /// normalization to find the start of the messages, and `days_first`,
+    /// `preferred_order`, `custom_message_regex` and `custom_system_regex` are
+    /// ignored. Every function taking options uses it; the ones that don't,
+    /// such as [`parse_string_count`](crate::parse_string_count), only
+    /// recognize the built-in formats.
+    pub date_time_parser: Option<Box<dyn DateTimeParser>>,
+    /// Two digit years below this value are in the 2000s, the others in the
+    /// 1900s.
+    ///
+    /// Defaults to `70`, so `69` is 2069 and `70` is 1970.
+    pub two_digit_year_cutoff: u8,
+    /// A regex matching the lines that start user messages, for exports that
+    /// don't follow any WhatsApp format.
+    ///
+    /// It must capture the date (group 1), the time (2), an optional AM/PM
+    /// marker (3), the author (4) and the message (5). The date and time are
+    /// then normalized like the built-in ones.
+    ///
+    /// The date must have 3 numeric components and the time hours and minutes,
+    /// separated by `:` or `.`. Messages whose captures don't are dated
+    /// 1970-01-01, like the ones with impossible dates.
+    ///
+    /// Used by [`parse_string`](crate::parse_string) and the functions built
+    /// on it; the counting functions always use the built-in regexes.
+    pub custom_message_regex: Option<Regex>,
+    /// A regex matching the lines that start system messages, see
+    /// `custom_message_regex`.
+    ///
+    /// It must capture the date (group 1), the time (2), an optional AM/PM
+    /// marker (3) and the message (4). Lines matching `custom_message_regex`
+    /// are user messages even if they match this too.
+    pub custom_system_regex: Option<Regex>,
+    /// Month names mapped to their number (`1` for January), for exports
+    /// writing the month in words, e.g. `7. Januar 2023 14:30 - `.
+    ///
+    /// If set, dates with these month names are recognized instead of numeric
+    /// dates, with days before months, like with a `date_time_parser`.
+    /// Names are matched without regard to case. See
+    /// `datetime::month_map_for_locale` for the names of common locales.
+    /// Ignored when `date_time_parser` is set.
+    pub locale_month_names: Option<HashMap<String, u32>>,
+}
+
+impl Default for ParseStringOptions {
+    fn default() -> Self {
+        ParseStringOptions {
+            days_first: None,
+            preferred_order: DateOrder::default(),
+            parse_attachments: false,
+            attachments_anywhere: false,
+            star_markers: Vec::new(),
+            drop_empty: false,
+            known_authors: Vec::new(),
+            debug: false,
+            log_sink: None,
+            max_bytes: None,
+            max_lines: None,
+            on_parse_error: ErrorPolicy::default(),
+            #[cfg(feature = "parallel")]
+            thread_pool: None,
+            #[cfg(feature = "parallel")]
+            parallel_threshold: None,
+            progress_callback: None,
+            progress_report_interval: 1000,
+            date_time_parser: None,
+            two_digit_year_cutoff: DEFAULT_TWO_DIGIT_YEAR_CUTOFF,
+            custom_message_regex: None,
+            custom_system_regex: None,
+            locale_month_names: None,
+        }
+    }
+}
+
+impl fmt::Debug for ParseStringOptions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut debug = f.debug_struct("ParseStringOptions");
+        debug
+            .field("days_first", &self.days_first)
+            .field("preferred_order", &self.preferred_order)
+            .field("parse_attachments", &self.parse_attachments)
+            .field("attachments_anywhere", &self.attachments_anywhere)
+            .field("star_markers", &self.star_markers)
+            .field("drop_empty", &self.drop_empty)
+            .field("known_authors", &self.known_authors)
+            .field("debug", &self.debug)
+            .field("log_sink", &self.log_sink.as_ref().map(|_| "Fn(&str)"))
+            .field("max_bytes", &self.max_bytes)
+            .field("max_lines", &self.max_lines)
+            .field("on_parse_error", &self.on_parse_error);
+        #[cfg(feature = "parallel")]
+        debug
+            .field("thread_pool", &self.thread_pool)
+            .field("parallel_threshold", &self.parallel_threshold);
+        debug
+            .field("progress_callback", &self.progress_callback.as_ref().map(|_| "Fn(usize, usize)"))
+            .field("progress_report_interval", &self.progress_report_interval)
+            .field("date_time_parser", &self.date_time_parser.as_ref().map(|_| "dyn DateTimeParser"))
+            .field("two_digit_year_cutoff", &self.two_digit_year_cutoff)
+            .field("custom_message_regex", &self.custom_message_regex)
+            .field("custom_system_regex", &self.custom_system_regex)
+            .field("locale_month_names", &self.locale_month_names)
+            .finish()
+    }
+}
+
+impl ParseStringOptions {
+    /// Default options, except messages are parsed on a private thread pool
+    /// with exactly `n` worker threads.
+    ///
+    /// Panics if the thread pool can't be created.
+    #[cfg(feature = "parallel")]
+    pub fn with_thread_count(n: usize) -> Self {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(n)
+            .build()
+            .expect("Failed to create the thread pool");
+        ParseStringOptions {
+            thread_pool: Some(Arc::new(pool)),
+            ..Default::default()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{TimeZone, Timelike};
+    use std::collections::{BTreeSet, HashSet};
+    use std::hash::DefaultHasher;
+    use std::path::Path;
+
+    fn message(text: &str) -> Message {
+        Message {
+            date: DateTime::<Utc>::default(),
+            author: Some("a".to_string()),
+            message: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_message_timestamp_unix() {
+        let sent = Message {
+            date: Utc.with_ymd_and_hms(2018, 6, 23, 13, 55, 12).unwrap(),
+            ..message("Hey!")
+        };
+        assert_eq!(sent.timestamp_unix(), 1_529_762_112);
+        assert_eq!(sent.timestamp_unix_millis(), 1_529_762_112_000);
+
+        let copy = Message::from_timestamp_unix(sent.timestamp_unix(), sent.author.clone(), sent.message.clone());
+        assert_eq!(copy, sent);
+
+        let epoch = Message::from_timestamp_unix(0, None, "Luke added Leia".to_string());
+        assert_eq!(epoch.date.to_rfc3339(), "1970-01-01T00:00:00+00:00");
+        assert!(epoch.is_system());
+        assert_eq!(Message::from_timestamp_unix(i64::MAX, None, String::new()).timestamp_unix(), 0);
+    }
+
+    #[test]
+    fn test_message_mentions() {
+        assert_eq!(message("Hey @Loris and @41791234567, lunch?").mentions(), vec!["Loris", "41791234567"]);
+        assert_eq!(message("ping @+41791234567.").mentions(), vec!["+41791234567"]);
+        assert_eq!(message("@\u{2068}Luke Skywalker\u{2069} look").mentions(), vec!["Luke Skywalker"]);
+        assert!(message("mail me at someone@example.com").mentions().is_empty());
+        assert!(message("no mentions here").mentions().is_empty());
+    }
+
+    #[test]
+    fn test_attachment_mime_type() {
+        let mime_type = |file_name: &str| {
+            Attachment {
+                file_name: file_name.to_string(),
+                ..Default::default()
+            }
+            .mime_type()
+        };
+
+        assert_eq!(mime_type("IMG-20180623-WA0001.jpg"), "image/jpeg");
+        assert_eq!(mime_type("photo.JPEG"), "image/jpeg");
+        assert_eq!(mime_type("screenshot.png"), "image/png");
+        assert_eq!(mime_type("STK-20180623-WA0001.webp"), "image/webp");
+        assert_eq!(mime_type("VID-20180623-WA0001.mp4"), "video/mp4");
+        assert_eq!(mime_type("song.mp3"), "audio/mpeg");
+        assert_eq!(mime_type("PTT-20180623-WA0001.ogg"), "audio/ogg");
+        assert_eq!(mime_type("document.pdf"), "application/pdf");
+        assert_eq!(mime_type("Luke Skywalker.vcf"), "text/vcard");
+        assert_eq!(mime_type("archive.xyz"), "application/octet-stream");
+        assert_eq!(mime_type("no_extension"), "application/octet-stream");
+    }
+
+    #[test]
+    fn test_attachment_full_path() {
+        let full_path = |file_name: &str| {
+            Attachment {
+                file_name: file_name.to_string(),
+                ..Default::default()
+            }
+            .full_path(Path::new("/exports"))
+        };
+
+        assert_eq!(full_path("photo.jpg"), Path::new("/exports/photo.jpg"));
+        assert_eq!(full_path("../photo.jpg"), Path::new("/exports/photo.jpg"));
+        assert_eq!(full_path("../../etc/../photo.jpg"), Path::new("/exports/etc/photo.jpg"));
+        assert_eq!(full_path("/photo.jpg"), Path::new("/exports/photo.jpg"));
+        assert_eq!(full_path("media/./photo.jpg"), Path::new("/exports/media/photo.jpg"));
+    }
+
+    #[test]
+    fn test_attachment_extension() {
+        let attachment = |file_name: &str| Attachment {
+            file_name: file_name.to_string(),
+            ..Default::default()
+        };
+
+        assert_eq!(attachment("IMG-20180623-WA0001.jpg").extension(), Some("jpg"));
+        assert_eq!(attachment("archive.tar.gz").extension(), Some("gz"));
+        assert_eq!(attachment(".gitignore").extension(), None);
+        assert_eq!(attachment("").extension(), None);
+
+        let photo = attachment("photo.png");
+        let extension = photo.extension().unwrap();
+        assert!(photo.file_name.as_bytes().as_ptr_range().contains(&extension.as_ptr()));
+    }
+
+    #[test]
+    fn test_message_kind() {
+        let system = Message {
+            author: None,
+            ..message("You created group \"Test\"")
+        };
+        let text = message("Hey!");
+        let omitted = message("\u{200E}<Media omitted>");
+        let with_attachment = Message {
+            attachment: Some(Attachment {
+                file_name: "IMG-20180623-WA0001.jpg".to_string(),
+                ..Default::default()
+            }),
+            ..message("IMG-20180623-WA0001.jpg (file attached)")
+        };
+
+        assert!(system.is_system() && !system.is_user() && !system.is_media());
+        assert!(!text.is_system() && text.is_user() && !text.is_media());
+        assert!(omitted.is_user() && omitted.is_media());
+        assert!(with_attachment.is_user() && with_attachment.is_media());
+        for message in [&system, &text, &omitted, &with_attachment] {
+            assert!(!(message.is_system() && message.is_user()));
+            assert!(message.is_system() || message.is_user());
+        }
+    }
+
+    #[test]
+    fn test_message_word_count() {
+        assert_eq!(message("").word_count(), 0);
+        assert_eq!(message(" \n\t ").word_count(), 0);
+        assert_eq!(message("Hey!").word_count(), 1);
+        assert_eq!(message("How are you?\nIs everything alright?").word_count(), 6);
+        // No spaces between the words, so the whole sentence counts as one
+        assert_eq!(message("今日は元気です").word_count(), 1);
+        assert_eq!(message("?!").word_count(), 1);
+        assert_eq!(message("... ?").word_count(), 2);
+    }
+
+    #[test]
+    fn test_message_ordering() {
+        let at = |hour: u32, author: Option<&str>, text: &str| Message {
+            date: Utc.with_ymd_and_hms(2021, 6, 23, hour, 0, 0).unwrap(),
+            author: author.map(|a| a.to_string()),
+            message: text.to_string(),
+            ..Default::default()
+        };
+        let messages = [
+            at(12, Some("b"), "x"),
+            at(9, Some("a"), "y"),
+            at(12, Some("a"), "z"),
+            at(12, None, "system"),
+            at(12, Some("a"), "w"),
+        ];
+        let expected = vec![
+            at(9, Some("a"), "y"),
+            at(12, None, "system"),
+            at(12, Some("a"), "w"),
+            at(12, Some("a"), "z"),
+            at(12, Some("b"), "x"),
+        ];
+
+        let set: BTreeSet<Message> = messages.into_iter().collect();
+        assert_eq!(set.into_iter().collect::<Vec<_>>(), expected);
+
+        let mut messages = [at(12, Some("b"), "x"), at(9, Some("a"), "y"), at(10, Some("c"), "z")];
+        messages.sort();
+        let hours: Vec<u32> = messages.iter().map(|m| m.date.hour()).collect();
+        assert_eq!(hours, vec![9, 10, 12]);
+    }
+
+    #[test]
+    fn test_message_hash() {
+        let hash = |message: &Message| {
+            let mut hasher = DefaultHasher::new();
+            message.hash(&mut hasher);
+            hasher.finish()
+        };
+
+        assert_eq!(hash(&message("Hey!")), hash(&message("Hey!")));
+        assert_ne!(hash(&message("Hey!")), hash(&message("Hey?")));
+
+        let set: HashSet<Message> = [message("Hey!"), message("Hey!")].into_iter().collect();
+        assert_eq!(set.len(), 1);
+    }
 }
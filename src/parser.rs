@@ -1,14 +1,40 @@
-use crate::datetime::{days_before_months, normalize_date, order_date_components, convert_time_12_to_24, normalize_ampm, normalize_time};
-use crate::models::{Attachment, Message, ParseStringOptions, RawMessage};
+use crate::datetime::{DateTimeParser, days_before_months, normalize_date_with_cutoff, normalize_digits, order_date_components, convert_time_12_to_24, normalize_ampm, normalize_time};
+use crate::error::{ParseError, ParseLineError, ParseLineErrorReason, ParseWarning, TimeParseError};
+use crate::system::classify_system_message;
+use crate::models::{Attachment, ErrorPolicy, LogSink, MediaKind, Message, ParseStats, ParseStringOptions, RawMessage};
+use chrono::{DateTime, NaiveDate, NaiveDateTime, NaiveTime, Utc};
 use lazy_static::lazy_static;
 use regex::Regex;
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+#[cfg(feature = "parallel")]
 use rayon::prelude::*;
 
+/// What follows the file name of an attachment: an optional file size, then
+/// `(file attached)`, its German version or the `<attached>` of iOS exports.
+const ATTACHMENT_MARKER: &str = r"(?:\s\(\d+(?:[.,]\d+)?\s?(?i:[KMGT]?B)\))?\s+(?:\((?:file attached|Datei angehängt)\)|<attached>)";
+
 lazy_static! {
     static ref SHARED_REGEX: Regex = Regex::new(r"^(?:\u{200E}|\u{200F})*\[?(\d{1,4}[-/.]\s?\d{1,4}[-/.]\s?\d{1,4})[,.]?\s\D*?(\d{1,2}[.:]\d{1,2}(?:[.:]\d{1,2})?)(?:(?:\s|\u{202F})([AaPp](?:\.\s?|\s?)[Mm]\.?))?\]?(?:\s-|:)?\s").unwrap();
+    // Dates with a written month, e.g. `7. Januar 2023 14:30 - ` or `7 de enero de 2023, 14:30 - `
+    static ref MONTH_NAME_REGEX: Regex = Regex::new(r"^(?:\u{200E}|\u{200F})*\[?(\d{1,2})\.?\s(?:de\s)?(\p{L}+)\.?(?:\sde)?,?\s(\d{2,4})[,.]?\s\D*?(\d{1,2}[.:]\d{1,2}(?:[.:]\d{1,2})?)(?:(?:\s|\u{202F})([AaPp](?:\.\s?|\s?)[Mm]\.?))?\]?(?:\s-|:)?\s").unwrap();
+    // Splits at the first `: `, so bodies can contain `: ` but authors can't,
+    // see `ParseStringOptions::known_authors`
     static ref AUTHOR_AND_MESSAGE_REGEX: Regex = Regex::new(r"(?s)(.+?):\s(.*)").unwrap();
     static ref MESSAGE_REGEX: Regex = Regex::new(r"(?s)(.*)").unwrap();
-    static ref REGEX_ATTACHMENT: Regex = Regex::new(r"^(?:\u{200E}|\u{200F})*(?:<.+:(.+)>|([\w-]+\.\w+)\s[(<].+[)>])").unwrap();
+    // File names can contain spaces and several dots, e.g. `My Photo 2023.jpg`
+    // or `archive.tar.gz (file attached)`
+    static ref REGEX_ATTACHMENT: Regex = Regex::new(&format!(r"^(?:\u{{200E}}|\u{{200F}})*(?:<\s*attached:(.+)>|([\w'-][\w.' -]*\.\w+){})", ATTACHMENT_MARKER)).unwrap();
+    // Same as above, but the marker can follow a caption instead of starting the message.
+    // File names can't contain spaces here, they would swallow the caption.
+    static ref REGEX_ATTACHMENT_ANYWHERE: Regex = Regex::new(&format!(r"(?:^|\s)(?:\u{{200E}}|\u{{200F}})*(?:<\s*attached:(.+)>|([\w-][\w.-]*\.\w+){})", ATTACHMENT_MARKER)).unwrap();
+    // The names WhatsApp gives to media files, e.g. `IMG-20180623-WA0001.jpg`
+    // or `00000042-PHOTO-2020-06-07-15-13-20.jpg`
+    static ref REGEX_GENERATED_MEDIA_NAME: Regex = Regex::new(r"^(?:(?:IMG|VID|PTT|AUD|STK|DOC)-\d|[\d-]+-(?:PHOTO|VIDEO|AUDIO|GIF|STICKER)-)").unwrap();
+    // File size noted right after the filename, e.g. `IMG-1.jpg (2.3 MB) (file attached)`
+    static ref REGEX_ATTACHMENT_SIZE: Regex = Regex::new(r"(?i)^\s\((\d+(?:[.,]\d+)?)\s?([KMGT]?B)\)").unwrap();
+    // The placeholders of exports made without media, e.g. `\u{200E}sticker omitted`
+    static ref REGEX_MEDIA_OMITTED: Regex = Regex::new(r"^\s*(sticker|audio|image|video) omitted\s*$").unwrap();
     // Precompiled full regexes to avoid runtime compilation cost on each function call
     static ref REGEX_USER: Regex = Regex::new(&format!("{}{}", SHARED_REGEX.as_str(), AUTHOR_AND_MESSAGE_REGEX.as_str())).unwrap();
     static ref REGEX_SYSTEM: Regex = Regex::new(&format!("{}{}", SHARED_REGEX.as_str(), MESSAGE_REGEX.as_str())).unwrap();
@@ -28,301 +54,1022 @@ fn get_full_regex(is_system: bool) -> Regex {
     Regex::new(&pattern).unwrap()
 }
 
-/// Takes an array of lines and detects the lines that are part of a previous
-/// message (multiline messages) and merges them.
+/// Iterator over the lines of a chat export, accepting both `\n` and `\r\n`
+/// line endings.
+///
+/// Yields the same lines as `s.split('\n')` with the trailing `\r` removed,
+/// but finds the line breaks with `memchr` and doesn't need to collect the
+/// lines up front. A leading UTF-8 byte order mark is dropped, otherwise the
+/// first message would not be recognized.
+pub struct Lines<'a> {
+    rest: Option<&'a str>,
+}
+
+impl<'a> Lines<'a> {
+    pub fn new(s: &'a str) -> Self {
+        Lines {
+            rest: Some(s.strip_prefix('\u{FEFF}').unwrap_or(s)),
+        }
+    }
+}
+
+impl<'a> Iterator for Lines<'a> {
+    type Item = &'a str;
+
+    fn next(&mut self) -> Option<&'a str> {
+        let rest = self.rest?;
+        let line = match memchr::memchr(b'\n', rest.as_bytes()) {
+            Some(idx) => {
+                self.rest = Some(&rest[idx + 1..]);
+                &rest[..idx]
+            }
+            None => {
+                self.rest = None;
+                rest
+            }
+        };
+        Some(line.trim_end_matches('\r'))
+    }
+}
+
+/// Checks if a line starts a user message, i.e. has a date, a time and an
+/// author. Uses the same regex as the parser.
+pub fn is_message_start(line: &str) -> bool {
+    REGEX_USER.is_match(line)
+}
+
+/// Checks if a line starts a message with a date and a time, with or without
+/// an author. Uses the same regex as the parser, which treats the lines
+/// matching this but not [`is_message_start`] as system messages.
+pub fn is_system_message_start(line: &str) -> bool {
+    REGEX_SYSTEM.is_match(line)
+}
+
+/// Takes the lines of an export and detects the lines that are part of a
+/// previous message (multiline messages) and merges them.
 ///
 /// It also labels messages without an author as system messages.
-pub fn make_array_of_messages(lines: &[&str]) -> Vec<RawMessage> {
+pub fn make_array_of_messages<'a, I: Iterator<Item = &'a str>>(lines: I) -> Vec<RawMessage> {
     make_array_of_messages_with_debug(lines, false)
 }
 
-/// Takes an array of lines and detects the lines that are part of a previous
-/// message (multiline messages) and merges them with optional debug output.
+/// Same as [`make_array_of_messages`], but takes an array of lines.
+pub fn make_array_of_messages_slice(lines: &[&str]) -> Vec<RawMessage> {
+    make_array_of_messages(lines.iter().copied())
+}
+
+/// Takes the lines of an export and detects the lines that are part of a
+/// previous message (multiline messages) and merges them with optional debug
+/// output.
 ///
 /// It also labels messages without an author as system messages.
-pub fn make_array_of_messages_with_debug(lines: &[&str], debug: bool) -> Vec<RawMessage> {
-    let mut acc: Vec<RawMessage> = Vec::new();
-    let regex_parser = &*REGEX_USER;
-    let regex_parser_system = &*REGEX_SYSTEM;
+pub fn make_array_of_messages_with_debug<'a, I: Iterator<Item = &'a str>>(lines: I, debug: bool) -> Vec<RawMessage> {
+    aggregate_lines(lines, debug).0
+}
 
-    if debug {
-        println!("🔍 DEBUG: Starting message aggregation with {} lines", lines.len());
-        println!("🔍 DEBUG: User message regex: {}", regex_parser.as_str());
-        println!("🔍 DEBUG: System message regex: {}", regex_parser_system.as_str());
-        println!("🔍 DEBUG: =====================================");
+/// Same as [`make_array_of_messages_with_debug`], but also returns the lines
+/// that had to be discarded because they are not part of any message.
+pub fn make_array_of_messages_with_errors(lines: &[&str], debug: bool) -> (Vec<RawMessage>, Vec<ParseLineError>) {
+    aggregate_lines(lines.iter().copied(), debug)
+}
+
+/// Same as [`make_array_of_messages_with_errors`], but takes the lines from an
+/// iterator, e.g. [`Lines`], so they don't have to be collected first.
+pub fn aggregate_lines<'a>(lines: impl IntoIterator<Item = &'a str>, debug: bool) -> (Vec<RawMessage>, Vec<ParseLineError>) {
+    aggregate_lines_with_regexes(lines, &REGEX_USER, &REGEX_SYSTEM, DebugLog::stdout(debug), &mut ParseStats::default())
+}
+
+/// Same as [`aggregate_lines`], but recognizes the start of the messages with
+/// the date parser or the custom regexes of the options, if set.
+pub fn aggregate_lines_with_options<'a>(lines: impl IntoIterator<Item = &'a str>, options: &ParseStringOptions) -> (Vec<RawMessage>, Vec<ParseLineError>) {
+    if let Some(date_parser) = options_date_parser(options) {
+        return aggregate_lines_with_parser(lines, &date_parser, options, &mut ParseStats::default());
+    }
+    let (user_regex, system_regex) = message_regexes(options);
+    aggregate_lines_with_regexes(lines, user_regex, system_regex, DebugLog::new(options), &mut ParseStats::default())
+}
+
+/// Same as [`aggregate_lines_with_options`], but also counts the lines by the
+/// way they were handled.
+pub fn aggregate_lines_with_stats<'a>(lines: impl IntoIterator<Item = &'a str>, options: &ParseStringOptions) -> (Vec<RawMessage>, Vec<ParseLineError>, ParseStats) {
+    let (user_regex, system_regex) = message_regexes(options);
+    let mut stats = ParseStats::default();
+    let (messages, errors) = match options_date_parser(options) {
+        Some(date_parser) => aggregate_lines_with_parser(lines, &date_parser, options, &mut stats),
+        None => aggregate_lines_with_regexes(lines, user_regex, system_regex, DebugLog::new(options), &mut stats),
+    };
+    (messages, errors, stats)
+}
+
+/// The regexes matching the start of user and system messages: the custom ones
+/// of the options if set, otherwise the built-in ones.
+fn message_regexes(options: &ParseStringOptions) -> (&Regex, &Regex) {
+    (
+        options.custom_message_regex.as_ref().unwrap_or(&REGEX_USER),
+        options.custom_system_regex.as_ref().unwrap_or(&REGEX_SYSTEM),
+    )
+}
+
+/// Copies the first line of a message, leaving room for continuation lines.
+fn message_buffer(line: &str) -> String {
+    let mut msg = String::with_capacity(line.len() * 2);
+    msg.push_str(line);
+    msg
+}
+
+fn aggregate_lines_with_regexes<'a>(lines: impl IntoIterator<Item = &'a str>, regex_parser: &Regex, regex_parser_system: &Regex, log: DebugLog, stats: &mut ParseStats) -> (Vec<RawMessage>, Vec<ParseLineError>) {
+    if log.enabled() {
+        log.write("🔍 DEBUG: Starting message aggregation");
+        log.write(&format!("🔍 DEBUG: User message regex: {}", regex_parser.as_str()));
+        log.write(&format!("🔍 DEBUG: System message regex: {}", regex_parser_system.as_str()));
     }
 
-    for (line_idx, line) in lines.iter().enumerate() {
-        if debug {
-            println!("🔍 DEBUG: Processing line {}: '{}'", line_idx + 1, line);
+    aggregate_lines_by(lines, |line| {
+        if regex_parser.is_match(line) {
+            Some(false)
+        } else if regex_parser_system.is_match(line) {
+            Some(true)
+        } else {
+            None
+        }
+    }, log, stats)
+}
+
+/// Groups lines into raw messages, recognizing the start of the messages and
+/// their authors with a [`DateTimeParser`].
+fn aggregate_lines_with_parser<'a>(lines: impl IntoIterator<Item = &'a str>, date_parser: &dyn DateTimeParser, options: &ParseStringOptions, stats: &mut ParseStats) -> (Vec<RawMessage>, Vec<ParseLineError>) {
+    let log = DebugLog::new(options);
+    if log.enabled() {
+        log.write("🔍 DEBUG: Starting message aggregation");
+        log.write("🔍 DEBUG: Using the date parser of the options");
+    }
+
+    aggregate_lines_by(lines, |line| {
+        let (_, consumed) = date_parser.parse(line)?;
+        Some(split_author(line.get(consumed..).unwrap_or(""), &options.known_authors).is_none())
+    }, log, stats)
+}
+
+/// Groups lines into raw messages. `message_start` tells if a line starts a
+/// system message (`Some(true)`), a user message (`Some(false)`) or neither,
+/// in which case it continues the previous message.
+fn aggregate_lines_by<'a>(lines: impl IntoIterator<Item = &'a str>, message_start: impl Fn(&str) -> Option<bool>, log: DebugLog, stats: &mut ParseStats) -> (Vec<RawMessage>, Vec<ParseLineError>) {
+    let lines = lines.into_iter();
+    // Most exports have at least one message every two lines
+    let mut acc: Vec<RawMessage> = Vec::with_capacity(lines.size_hint().0 / 2);
+    let mut errors: Vec<ParseLineError> = Vec::new();
+
+    if log.enabled() {
+        log.write("🔍 DEBUG: =====================================");
+    }
+
+    for (line_idx, line) in lines.enumerate() {
+        stats.total_lines += 1;
+        if log.enabled() {
+            log.write(&format!("🔍 DEBUG: Processing line {}: '{}'", line_idx + 1, line));
         }
         
-        if !regex_parser.is_match(line) {
-            if regex_parser_system.is_match(line) {
-                if debug {
-                    println!("🔍 DEBUG: ✓ Detected system message");
+        match message_start(line) {
+            Some(system) => {
+                if log.enabled() {
+                    log.write(if system { "🔍 DEBUG: ✓ Detected system message" } else { "🔍 DEBUG: ✓ Detected user message" });
+                }
+                if system {
+                    stats.system_message_starts += 1;
+                } else {
+                    stats.message_starts += 1;
                 }
                 acc.push(RawMessage {
-                    system: true,
-                    msg: line.to_string(),
+                    system,
+                    msg: message_buffer(line),
+                    line_start: line_idx + 1,
+                    line_end: line_idx + 1,
                 });
-            } else if let Some(prev_message) = acc.last_mut() {
-                if debug {
-                    println!("🔍 DEBUG: ↪ Appending to previous message (multiline)");
-                }
-                prev_message.msg.push('\n');
-                prev_message.msg.push_str(line);
-            } else {
-                if debug {
-                    println!("🔍 DEBUG: ⚠ Line doesn't match any pattern and no previous message exists");
-                }
             }
-        } else {
-            if debug {
-                println!("🔍 DEBUG: ✓ Detected user message");
+            None => {
+                if let Some(prev_message) = acc.last_mut() {
+                    if log.enabled() {
+                        log.write("🔍 DEBUG: ↪ Appending to previous message (multiline)");
+                    }
+                    stats.continuation_lines += 1;
+                    prev_message.msg.push('\n');
+                    prev_message.msg.push_str(line);
+                    prev_message.line_end = line_idx + 1;
+                } else {
+                    if log.enabled() {
+                        log.write("🔍 DEBUG: ⚠ Line doesn't match any pattern and no previous message exists");
+                    }
+                    stats.discarded_lines += 1;
+                    errors.push(ParseLineError {
+                        line_number: line_idx + 1,
+                        raw_line: line.to_string(),
+                        reason: ParseLineErrorReason::NoPrecedingMessage,
+                    });
+                }
             }
-            acc.push(RawMessage {
-                system: false,
-                msg: line.to_string(),
-            });
         }
     }
 
-    if debug {
-        println!("🔍 DEBUG: =====================================");
-        println!("🔍 DEBUG: Message aggregation complete!");
-        println!("🔍 DEBUG: Total messages found: {}", acc.len());
+    if log.enabled() {
+        log.write("🔍 DEBUG: =====================================");
+        log.write("🔍 DEBUG: Message aggregation complete!");
+        log.write(&format!("🔍 DEBUG: Total lines processed: {}", stats.total_lines));
+        log.write(&format!("🔍 DEBUG: Total messages found: {}", acc.len()));
         let system_count = acc.iter().filter(|m| m.system).count();
         let user_count = acc.len() - system_count;
-        println!("🔍 DEBUG: - User messages: {}", user_count);
-        println!("🔍 DEBUG: - System messages: {}", system_count);
-        println!("🔍 DEBUG: =====================================");
+        log.write(&format!("🔍 DEBUG: - User messages: {}", user_count));
+        log.write(&format!("🔍 DEBUG: - System messages: {}", system_count));
+        log.write("🔍 DEBUG: =====================================");
     }
 
-    acc
+    (acc, errors)
+}
+
+/// Counts the messages in a sequence of lines without parsing them: every line
+/// starting with a date and time starts a new message, user or system.
+///
+/// Gives the same result as `make_array_of_messages_slice(lines).len()`.
+pub fn count_messages<'a>(lines: impl IntoIterator<Item = &'a str>) -> usize {
+    lines.into_iter().filter(|line| SHARED_REGEX.is_match(line)).count()
+}
+
+/// Collects the names of everyone who sent a message in a sequence of lines,
+/// without parsing the messages themselves.
+///
+/// Names are cleaned up the same way [`parse_messages`] does, deduplicated and
+/// sorted. System messages have no author and are skipped.
+pub fn collect_authors<'a>(lines: impl IntoIterator<Item = &'a str>) -> Vec<String> {
+    let authors: HashSet<String> = lines
+        .into_iter()
+        .filter_map(|line| REGEX_USER.captures(line))
+        .filter_map(|caps| caps.get(4).map(|m| strip_directional_marks(m.as_str()).trim().to_string()))
+        .collect();
+
+    let mut authors: Vec<String> = authors.into_iter().collect();
+    authors.sort();
+    authors
 }
 
 /// Parses a message extracting the attachment if it's present.
-fn parse_message_attachment(message: &str) -> Option<Attachment> {
-    REGEX_ATTACHMENT.captures(message).map(|caps| Attachment {
-        file_name: caps
-            .get(1)
-            .or_else(|| caps.get(2))
-            .map_or(String::new(), |m| m.as_str().trim().to_string()),
+///
+/// If `anywhere` is `true` the attachment marker can appear anywhere in the
+/// message, not only at its start. Directional marks are ignored, wherever
+/// they appear in the marker.
+///
+/// Messages made only of a media placeholder, such as `sticker omitted`, give
+/// an attachment with the media type as file name and the matching kind.
+fn parse_message_attachment(message: &str, anywhere: bool) -> Option<Attachment> {
+    let regex = if anywhere { &*REGEX_ATTACHMENT_ANYWHERE } else { &*REGEX_ATTACHMENT };
+    let message = strip_directional_marks(message);
+    if let Some(caps) = REGEX_MEDIA_OMITTED.captures(&message) {
+        let kind = match &caps[1] {
+            "sticker" => MediaKind::Sticker,
+            "audio" => MediaKind::Audio,
+            "image" => MediaKind::Image,
+            _ => MediaKind::Video,
+        };
+        return Some(Attachment {
+            file_name: caps[1].to_string(),
+            size: None,
+            kind: Some(kind),
+        });
+    }
+    let caps = regex.captures(&message)?;
+    if caps.get(2).is_some_and(|m| has_caption(m.as_str())) {
+        return None;
+    }
+    let file_name = caps.get(1).or_else(|| caps.get(2));
+    Some(Attachment {
+        file_name: file_name.map_or(String::new(), |m| m.as_str().trim().to_string()),
+        size: caps.get(2).and_then(|m| parse_attachment_size(&message[m.end()..])),
+        kind: None,
     })
 }
 
-/// Parses and array of raw messages into an array of structured objects.
-pub fn parse_messages(messages: &[RawMessage], options: &ParseStringOptions) -> Vec<Message> {
-    let mut days_first = options.days_first;
-    let parse_attachments = options.parse_attachments;
-    let debug = options.debug;
+/// Whether a file name with spaces ends with a name generated by WhatsApp,
+/// e.g. `Check this out IMG-1.jpg`. Generated names never follow other words,
+/// so these are a caption and not part of the file name.
+fn has_caption(file_name: &str) -> bool {
+    file_name.rsplit_once(' ').is_some_and(|(_, last)| REGEX_GENERATED_MEDIA_NAME.is_match(last))
+}
+
+/// Parses a file size such as ` (2.3 MB)` at the start of `text` into bytes,
+/// using powers of 1024 for the units.
+fn parse_attachment_size(text: &str) -> Option<u64> {
+    let caps = REGEX_ATTACHMENT_SIZE.captures(text)?;
+    let value: f64 = caps[1].replace(',', ".").parse().ok()?;
+    let exponent = match caps[2].to_uppercase().as_str() {
+        "B" => 0,
+        "KB" => 1,
+        "MB" => 2,
+        "GB" => 3,
+        _ => 4,
+    };
+    Some((value * 1024f64.powi(exponent)).round() as u64)
+}
+
+/// The components extracted from a raw message by the full regex: date, time,
+/// optional AM/PM marker, optional author and the cleaned up message body.
+type MessageParts = (String, String, Option<String>, Option<String>, String);
+
+/// The date, time and optional AM/PM marker at the start of a message.
+type HeaderParts<'a> = (&'a str, &'a str, Option<&'a str>);
+
+/// Extracts the date and time at the start of a message, if there is one, with
+/// the custom system message regex of the options if set.
+fn extract_header<'a>(line: &'a str, options: &ParseStringOptions) -> Option<HeaderParts<'a>> {
+    let regex = options.custom_system_regex.as_ref().unwrap_or(&SHARED_REGEX);
+    regex.captures(line).map(|caps| {
+        (
+            caps.get(1).map_or("", |m| m.as_str()),
+            caps.get(2).map_or("", |m| m.as_str()),
+            caps.get(3).map(|m| m.as_str()),
+        )
+    })
+}
+
+/// Where the debug output goes: the `log_sink` set in the options, or stdout.
+#[derive(Clone, Copy)]
+pub(crate) struct DebugLog<'a> {
+    enabled: bool,
+    sink: Option<&'a LogSink>,
+}
 
-    if debug {
-        println!("🔍 DEBUG: Starting message parsing with {} messages", messages.len());
-        println!("🔍 DEBUG: Options - days_first: {:?}, parse_attachments: {}", days_first, parse_attachments);
-        println!("🔍 DEBUG: =====================================");
+impl<'a> DebugLog<'a> {
+    /// The debug output enabled by the `debug` option.
+    pub(crate) fn new(options: &'a ParseStringOptions) -> Self {
+        DebugLog {
+            enabled: options.debug,
+            sink: options.log_sink.as_deref(),
+        }
     }
 
-    // Precompiled regexes for user and system messages (static)
-    let regex_user = &*REGEX_USER;
-    let regex_system = &*REGEX_SYSTEM;
+    /// Debug output printed to stdout, for the functions taking a `debug` flag
+    /// instead of the options.
+    fn stdout(enabled: bool) -> Self {
+        DebugLog { enabled, sink: None }
+    }
 
-    // Use parallel iterator for faster processing when debug is disabled
-    let parsed: Vec<_> = if debug {
-        messages
-            .iter()
-            .enumerate()
-            .map(|(msg_idx, obj)| {
-                // existing sequential logic
-                let (system, msg) = (&obj.system, &obj.msg);
-                let regex = if *system { regex_system } else { regex_user };
-                if debug {
-                    println!("🔍 DEBUG: Processing message {}: {} message", msg_idx + 1, if *system { "system" } else { "user" });
-                    println!("🔍 DEBUG: Raw message: '{}'", msg);
-                    println!("🔍 DEBUG: Using regex: {}", regex.as_str());
-                }
-                let caps = regex.captures(msg.as_ref()).unwrap();
-                let date = caps.get(1).map_or("", |m| m.as_str()).to_string();
-                let time = caps.get(2).map_or("", |m| m.as_str()).to_string();
-                let ampm = caps.get(3).map(|m| m.as_str().to_string());
-                let (author, message) = if *system {
-                    (None, caps.get(4).map_or("", |m| m.as_str()).to_string())
-                } else {
-                    (
-                        caps.get(4).map(|m| m.as_str().to_string()),
-                        caps.get(5).map_or("", |m| m.as_str()).to_string(),
-                    )
-                };
-                if debug {
-                    println!("🔍 DEBUG: Extracted components:\n - Date: '{}'\n - Time: '{}'\n - AM/PM: '{:?}'\n - Author: '{:?}'\n - Message (before cleanup): '{}'", date, time, ampm, author, message);
-                }
-                let message = message.replace('\u{200E}', "").replace('\u{200F}', "").trim().to_string();
-                (date, time, ampm, author, message)
-            })
-            .collect()
+    /// Whether debug messages should be generated at all.
+    pub(crate) fn enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Sends a debug message to the sink, or prints it.
+    pub(crate) fn write(&self, message: &str) {
+        match self.sink {
+            Some(sink) => sink(message),
+            None => println!("{}", message),
+        }
+    }
+}
+
+/// The smallest number of messages parsed in parallel when
+/// `parallel_threshold` is not set in the options. Below it, the cost of
+/// dispatching the work to the thread pool outweighs the work itself.
+pub const PARALLEL_THRESHOLD: usize = 50;
+
+/// Maps every item through `f`, in parallel unless debug output is enabled, in
+/// which case the items are processed sequentially to keep log output ordered.
+///
+/// Parallel work runs on the thread pool set in the options, if any, otherwise
+/// on the global Rayon pool. Fewer items than the parallel threshold of the
+/// options are processed sequentially, as is everything without the
+/// `parallel` feature.
+#[cfg(feature = "parallel")]
+fn map_items<'a, T, R, F>(items: &'a [T], options: &ParseStringOptions, f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(usize, &'a T) -> R + Sync + Send,
+{
+    if options.debug || items.len() < options.parallel_threshold.unwrap_or(PARALLEL_THRESHOLD) {
+        return items.iter().enumerate().map(|(idx, item)| f(idx, item)).collect();
+    }
+    let run = || items.par_iter().enumerate().map(|(idx, item)| f(idx, item)).collect();
+    if let Some(pool) = &options.thread_pool {
+        return pool.install(run);
+    }
+    run()
+}
+
+#[cfg(not(feature = "parallel"))]
+fn map_items<'a, T, R, F>(items: &'a [T], _options: &ParseStringOptions, f: F) -> Vec<R>
+where
+    F: Fn(usize, &'a T) -> R,
+{
+    items.iter().enumerate().map(|(idx, item)| f(idx, item)).collect()
+}
+
+/// Removes the left-to-right (U+200E) and right-to-left (U+200F) marks
+/// WhatsApp sprinkles around names and messages.
+///
+/// Other invisible characters, such as the zero width joiner (U+200D) used by
+/// emoji sequences, are preserved.
+pub(crate) fn strip_directional_marks(s: &str) -> Cow<'_, str> {
+    if s.contains(['\u{200E}', '\u{200F}']) {
+        s.replace(['\u{200E}', '\u{200F}'], "").into()
     } else {
-        messages
-            .par_iter()
-            .map(|obj| {
-                let (system, msg) = (&obj.system, &obj.msg);
-                let regex = if *system { regex_system } else { regex_user };
-                let caps = regex.captures(msg.as_ref()).unwrap();
-                let date = caps.get(1).map_or("", |m| m.as_str()).to_string();
-                let time = caps.get(2).map_or("", |m| m.as_str()).to_string();
-                let ampm = caps.get(3).map(|m| m.as_str().to_string());
-                let (author, message) = if *system {
-                    (None, caps.get(4).map_or("", |m| m.as_str()).to_string())
-                } else {
-                    (
-                        caps.get(4).map(|m| m.as_str().to_string()),
-                        caps.get(5).map_or("", |m| m.as_str()).to_string(),
-                    )
-                };
-                let message = message.replace('\u{200E}', "").replace('\u{200F}', "").trim().to_string();
-                (date, time, ampm, author, message)
-            })
-            .collect()
+        s.into()
+    }
+}
+
+/// Runs the full regex on a raw message and extracts its components.
+///
+/// Fails with `ParseError::MalformedMessage` if the message doesn't match the
+/// regex, which can only happen for raw messages built by hand.
+fn extract_message_parts(msg_idx: usize, obj: &RawMessage, options: &ParseStringOptions) -> Result<MessageParts, ParseError> {
+    let (system, msg) = (&obj.system, &obj.msg);
+    let log = DebugLog::new(options);
+    let (user_regex, system_regex) = message_regexes(options);
+    let regex = if *system { system_regex } else { user_regex };
+    if log.enabled() {
+        log.write(&format!("🔍 DEBUG: Processing message {}: {} message", msg_idx + 1, if *system { "system" } else { "user" }));
+        log.write(&format!("🔍 DEBUG: Raw message: '{}'", msg));
+        log.write(&format!("🔍 DEBUG: Using regex: {}", regex.as_str()));
+    }
+    let caps = regex
+        .captures(msg.as_ref())
+        .ok_or_else(|| ParseError::MalformedMessage(msg.clone()))?;
+    let date = caps.get(1).map_or("", |m| m.as_str()).to_string();
+    let time = caps.get(2).map_or("", |m| m.as_str()).to_string();
+    let ampm = caps.get(3).map(|m| m.as_str().to_string());
+    let (author, message) = if *system {
+        (None, caps.get(4).map_or("", |m| m.as_str()).to_string())
+    } else {
+        let known = caps.get(4).and_then(|m| split_known_author(&msg[m.start()..], &options.known_authors));
+        let (author, message) = known.unwrap_or_else(|| (caps.get(4).map_or("", |m| m.as_str()), caps.get(5).map_or("", |m| m.as_str())));
+        (Some(strip_directional_marks(author).trim().to_string()), message.to_string())
     };
+    if log.enabled() {
+        log.write(&format!("🔍 DEBUG: Extracted components:\n - Date: '{}'\n - Time: '{}'\n - AM/PM: '{:?}'\n - Author: '{:?}'\n - Message (before cleanup): '{}'", date, time, ampm, author, message));
+    }
+    let message = strip_directional_marks(&message).trim().to_string();
+    Ok((date, time, ampm, author, message))
+}
 
-    if days_first.is_none() {
-        if debug {
-            println!("🔍 DEBUG: Date format not specified, attempting auto-detection...");
-        }
-        let numeric_dates: Vec<Vec<i32>> = parsed
-            .iter()
-            .map(|(date, _, _, _, _)| {
-                let (d, m, y) = order_date_components(date);
-                vec![d.parse().unwrap(), m.parse().unwrap(), y.parse().unwrap()]
-            })
-            .collect();
-        days_first = days_before_months(&numeric_dates);
-        if debug {
-            println!("🔍 DEBUG: Date format auto-detection result: days_first = {:?}", days_first);
+/// Splits the author and body of a user message whose author is one of
+/// `known_authors`, for names containing `": "`. Picks the longest name
+/// followed by `": "`, ignoring directional marks around the name.
+///
+/// Output is `None` if the message doesn't start with any of the names.
+fn split_known_author<'a>(rest: &'a str, known_authors: &[String]) -> Option<(&'a str, &'a str)> {
+    let is_mark = |c| c == '\u{200E}' || c == '\u{200F}';
+    let name_start = rest.len() - rest.trim_start_matches(is_mark).len();
+    known_authors
+        .iter()
+        .filter(|name| !name.is_empty())
+        .filter_map(|name| {
+            let after = rest[name_start..].strip_prefix(name.as_str())?.trim_start_matches(is_mark);
+            let body = after.strip_prefix(':')?;
+            let separator = body.chars().next().filter(|c| c.is_whitespace())?;
+            Some((&rest[..rest.len() - after.len()], &body[separator.len_utf8()..]))
+        })
+        .max_by_key(|(author, _)| author.len())
+}
+
+/// Splits the rest of the first line of a user message, after its date, into
+/// author and body: with `known_authors` if one of them matches, otherwise at
+/// the first `": "`.
+///
+/// Output is `None` if the line has no author, i.e. is a system message.
+fn split_author<'a>(rest: &'a str, known_authors: &[String]) -> Option<(&'a str, &'a str)> {
+    split_known_author(rest, known_authors).or_else(|| {
+        let caps = AUTHOR_AND_MESSAGE_REGEX.captures(rest)?;
+        Some((caps.get(1).map_or("", |m| m.as_str()), caps.get(2).map_or("", |m| m.as_str())))
+    })
+}
+
+/// Removes the first of `markers` found at the start or at the end of a message
+/// body, returning the remaining body and whether a marker was found.
+fn strip_star_marker<'a>(message: &'a str, markers: &[String]) -> (&'a str, bool) {
+    for marker in markers.iter().filter(|m| !m.is_empty()) {
+        if let Some(rest) = message.strip_suffix(marker.as_str()).or_else(|| message.strip_prefix(marker.as_str())) {
+            return (rest.trim(), true);
         }
     }
+    (message, false)
+}
 
-    let final_messages: Vec<Message> = if debug {
-        parsed
+/// Applies the error policy set in the options to the results of a parsing
+/// step: failed items are either dropped or make the whole step fail.
+fn apply_error_policy<T>(results: Vec<Result<T, ParseError>>, options: &ParseStringOptions) -> Result<Vec<T>, ParseError> {
+    match options.on_parse_error {
+        ErrorPolicy::Fail => results.into_iter().collect(),
+        ErrorPolicy::Skip => Ok(results
             .into_iter()
-            .enumerate()
-            .map(|(msg_idx, (date, time, ampm, author, message))| {
-                if debug {
-                    println!("🔍 DEBUG: Creating final message object {}", msg_idx + 1);
-                }
-                // existing logic here (same as before)
-                let (day, month, year) = {
-                    let (d, m, y) = order_date_components(&date);
-                    if days_first == Some(false) {
-                        (m, d, y)
-                    } else {
-                        (d, m, y)
+            .filter_map(|result| match result {
+                Ok(item) => Some(item),
+                Err(err) => {
+                    let log = DebugLog::new(options);
+                    if log.enabled() {
+                        log.write(&format!("🔍 DEBUG: ⚠ Skipping message: {}", err));
                     }
-                };
-                let (year, month, day) = normalize_date(&year, &month, &day);
-                let time_normalized = if let Some(ampm_val) = ampm {
-                    normalize_time(&convert_time_12_to_24(&time, &normalize_ampm(&ampm_val)))
-                } else {
-                    normalize_time(&time)
-                };
-                if debug {
-                    println!("🔍 DEBUG: Date components: day={}, month={}, year={}", day, month, year);
-                    println!("🔍 DEBUG: Time normalized: {}", time_normalized);
+                    None
                 }
-                let final_date = {
-                    let day_u: u32 = day.parse().unwrap_or(1);
-                    let month_u: u32 = month.parse().unwrap_or(1);
-                    let year_i: i32 = year.parse().unwrap_or(1970);
-                    let mut time_split = time_normalized.split(':');
-                    let hour_u: u32 = time_split.next().unwrap_or("0").parse().unwrap_or(0);
-                    let minute_u: u32 = time_split.next().unwrap_or("0").parse().unwrap_or(0);
-                    let second_u: u32 = time_split.next().unwrap_or("0").parse().unwrap_or(0);
-                    let date = chrono::NaiveDate::from_ymd_opt(year_i, month_u, day_u).unwrap();
-                    let time = chrono::NaiveTime::from_hms_opt(hour_u, minute_u, second_u).unwrap();
-                    let naive_dt = date.and_time(time);
-                    chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(naive_dt, chrono::Utc)
-                };
-                let mut final_object = Message {
-                    date: final_date,
-                    author: author.clone(),
-                    message: message.clone(),
-                    attachment: None,
-                };
-                if parse_attachments {
-                    final_object.attachment = parse_message_attachment(&message);
-                }
-                final_object
             })
-            .collect()
+            .collect()),
+    }
+}
+
+/// The numeric components of a message date: year, month, day, hour, minute
+/// and second.
+type DateTimeComponents = (i32, u32, u32, u32, u32, u32);
+
+/// Converts the date and time components of a message into numbers, using
+/// `days_first` to decide the order of the date components.
+///
+/// The components may not make a valid date or time. Fails if they are not
+/// numbers, e.g. when they use digits that `normalize_digits` doesn't convert,
+/// if the date doesn't have 3 components or if the time has no minutes, which
+/// custom regexes can let through.
+fn datetime_components(date: &str, time: &str, ampm: Option<&str>, days_first: bool, year_cutoff: u8, log: DebugLog) -> Result<DateTimeComponents, Box<dyn std::error::Error>> {
+    let (date, time) = (normalize_digits(date), normalize_digits(time));
+    let (date, time) = (date.as_ref(), time.as_ref());
+    let (day, month, year) = {
+        let (d, m, y) = order_date_components(date)?;
+        if !days_first {
+            (m, d, y)
+        } else {
+            (d, m, y)
+        }
+    };
+    let (year, month, day) = normalize_date_with_cutoff(&year, &month, &day, year_cutoff)?;
+    if !time.contains([':', '.']) {
+        return Err(TimeParseError::MissingMinutes(time.to_string()).into());
+    }
+    let time_normalized = if let Some(ampm_val) = ampm {
+        normalize_time(&convert_time_12_to_24(time, &normalize_ampm(ampm_val))?)
     } else {
-        parsed
-            .into_par_iter()
-            .map(|(date, time, ampm, author, message)| {
-                let (day, month, year) = {
-                    let (d, m, y) = order_date_components(&date);
-                    if days_first == Some(false) {
-                        (m, d, y)
-                    } else {
-                        (d, m, y)
-                    }
-                };
-                let (year, month, day) = normalize_date(&year, &month, &day);
-                let time_normalized = if let Some(ampm_val) = ampm {
-                    normalize_time(&convert_time_12_to_24(&time, &normalize_ampm(&ampm_val)))
-                } else {
-                    normalize_time(&time)
-                };
-                let day_u: u32 = day.parse().unwrap_or(1);
-                let month_u: u32 = month.parse().unwrap_or(1);
-                let year_i: i32 = year.parse().unwrap_or(1970);
-                let mut time_split = time_normalized.split(':');
-                let hour_u: u32 = time_split.next().unwrap_or("0").parse().unwrap_or(0);
-                let minute_u: u32 = time_split.next().unwrap_or("0").parse().unwrap_or(0);
-                let second_u: u32 = time_split.next().unwrap_or("0").parse().unwrap_or(0);
-                let date = chrono::NaiveDate::from_ymd_opt(year_i, month_u, day_u).unwrap();
-                let time = chrono::NaiveTime::from_hms_opt(hour_u, minute_u, second_u).unwrap();
-                let naive_dt = date.and_time(time);
-                let final_date = chrono::DateTime::<chrono::Utc>::from_naive_utc_and_offset(naive_dt, chrono::Utc);
-                let mut final_object = Message {
-                    date: final_date,
-                    author: author.clone(),
-                    message: message.clone(),
-                    attachment: None,
-                };
-                if parse_attachments {
-                    final_object.attachment = parse_message_attachment(&message);
-                }
-                final_object
-            })
-            .collect()
+        normalize_time(time)
+    };
+    if log.enabled() {
+        log.write(&format!("🔍 DEBUG: Date components: day={}, month={}, year={}", day, month, year));
+        log.write(&format!("🔍 DEBUG: Time normalized: {}", time_normalized));
+    }
+    let day_u: u32 = day.parse().unwrap_or(1);
+    let month_u: u32 = month.parse().unwrap_or(1);
+    let year_i: i32 = year.parse().unwrap_or(1970);
+    let mut time_split = time_normalized.split(':');
+    let hour_u: u32 = time_split.next().unwrap_or("0").parse().unwrap_or(0);
+    let minute_u: u32 = time_split.next().unwrap_or("0").parse().unwrap_or(0);
+    let second_u: u32 = time_split.next().unwrap_or("0").parse().unwrap_or(0);
+    Ok((year_i, month_u, day_u, hour_u, minute_u, second_u))
+}
+
+/// Converts the date and time components of a message into a naive datetime,
+/// using `days_first` to decide the order of the date components.
+///
+/// Impossible dates (e.g. February 30 or month 13) and dates that can't be
+/// read fall back to 1970-01-01, and impossible times to midnight, instead of
+/// panicking.
+fn build_datetime(date: &str, time: &str, ampm: Option<&str>, days_first: bool, year_cutoff: u8, log: DebugLog) -> NaiveDateTime {
+    let (year, month, day, hour, minute, second) = datetime_components(date, time, ampm, days_first, year_cutoff, log).unwrap_or_else(|err| {
+        if log.enabled() {
+            log.write(&format!("🔍 DEBUG: ⚠ Invalid date ({}), falling back to 1970-01-01", err));
+        }
+        (1970, 1, 1, 0, 0, 0)
+    });
+    let date = NaiveDate::from_ymd_opt(year, month, day).unwrap_or_else(|| {
+        if log.enabled() {
+            log.write(&format!("🔍 DEBUG: ⚠ Invalid date (day={}, month={}, year={}), falling back to 1970-01-01", day, month, year));
+        }
+        NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()
+    });
+    let time = NaiveTime::from_hms_opt(hour, minute, second).unwrap_or_else(|| {
+        if log.enabled() {
+            log.write(&format!("🔍 DEBUG: ⚠ Invalid time (hour={}, minute={}, second={}), falling back to 00:00:00", hour, minute, second));
+        }
+        NaiveTime::MIN
+    });
+    date.and_time(time)
+}
+
+/// Checks if the date and time components of a message make a valid datetime,
+/// without falling back like [`build_datetime`] does.
+fn is_valid_datetime(date: &str, time: &str, ampm: Option<&str>, days_first: bool, year_cutoff: u8) -> bool {
+    let Ok((year, month, day, hour, minute, second)) = datetime_components(date, time, ampm, days_first, year_cutoff, DebugLog::stdout(false)) else {
+        return false;
+    };
+    NaiveDate::from_ymd_opt(year, month, day).is_some() && NaiveTime::from_hms_opt(hour, minute, second).is_some()
+}
+
+/// Tries to understand if the days come before the month in a list of raw
+/// dates, as captured by the full regex.
+fn detect_days_first<'a>(dates: impl Iterator<Item = &'a str>, log: DebugLog) -> Option<bool> {
+    if log.enabled() {
+        log.write("🔍 DEBUG: Date format not specified, attempting auto-detection...");
+    }
+    let numeric_dates: Vec<Vec<i32>> = dates
+        .filter_map(|date| {
+            let (d, m, y) = order_date_components(&normalize_digits(date)).ok()?;
+            Some(vec![d.parse().ok()?, m.parse().ok()?, y.parse().ok()?])
+        })
+        .collect();
+    let days_first = days_before_months(&numeric_dates);
+    if log.enabled() {
+        log.write(&format!("🔍 DEBUG: Date format auto-detection result: days_first = {:?}", days_first));
+    }
+    days_first
+}
+
+/// Extracts the components of every raw message and resolves the order of the
+/// date components, running the auto-detection if it wasn't specified.
+fn prepare_messages(messages: &[RawMessage], options: &ParseStringOptions) -> Result<(Vec<MessageParts>, bool), ParseError> {
+    let log = DebugLog::new(options);
+
+    if log.enabled() {
+        log.write(&format!("🔍 DEBUG: Starting message parsing with {} messages", messages.len()));
+        log.write(&format!("🔍 DEBUG: Options - days_first: {:?}, parse_attachments: {}", options.days_first, options.parse_attachments));
+        log.write("🔍 DEBUG: =====================================");
+    }
+
+    let parsed = map_items(messages, options, |msg_idx, obj| extract_message_parts(msg_idx, obj, options));
+    let parsed = apply_error_policy(parsed, options)?;
+    let days_first = options
+        .days_first
+        .or_else(|| detect_days_first(parsed.iter().map(|(date, _, _, _, _)| date.as_str()), log))
+        .unwrap_or(options.preferred_order.days_first());
+
+    Ok((parsed, days_first))
+}
+
+/// Builds a message from its date, author and cleaned up body, applying the
+/// star markers and attachment options.
+fn finish_message(date: NaiveDateTime, author: Option<String>, body: &str, options: &ParseStringOptions) -> Message {
+    let (body, starred) = strip_star_marker(body, &options.star_markers);
+    let system_kind = author.is_none().then(|| classify_system_message(body));
+    let attachment = if options.parse_attachments {
+        parse_message_attachment(body, options.attachments_anywhere)
+    } else {
+        None
     };
 
-    if debug {
-        println!("🔍 DEBUG: Message parsing complete!");
-        println!("🔍 DEBUG: Total messages processed: {}", final_messages.len());
-        let authors: std::collections::HashSet<_> = final_messages.iter()
+    Message {
+        date: DateTime::<Utc>::from_naive_utc_and_offset(date, Utc),
+        author,
+        message: body.to_string(),
+        attachment,
+        session_id: None,
+        starred,
+        system_kind,
+    }
+}
+
+/// Turns the extracted components of the messages into structured objects.
+fn build_messages(parsed: &[MessageParts], days_first: bool, options: &ParseStringOptions) -> Vec<Message> {
+    let log = DebugLog::new(options);
+
+    let mut final_messages: Vec<Message> = map_items(parsed, options, |msg_idx, (date, time, ampm, author, message)| {
+        if log.enabled() {
+            log.write(&format!("🔍 DEBUG: Creating final message object {}", msg_idx + 1));
+        }
+        let naive_dt = build_datetime(date, time, ampm.as_deref(), days_first, options.two_digit_year_cutoff, log);
+        finish_message(naive_dt, author.clone(), message, options)
+    });
+    if options.drop_empty {
+        final_messages.retain(|m| !m.message.is_empty() || m.attachment.is_some());
+    }
+
+    if log.enabled() {
+        log.write("🔍 DEBUG: Message parsing complete!");
+        log.write(&format!("🔍 DEBUG: Total messages processed: {}", final_messages.len()));
+        let authors: HashSet<_> = final_messages.iter()
             .filter_map(|m| m.author.as_ref())
             .collect();
-        println!("🔍 DEBUG: Unique authors: {}", authors.len());
+        log.write(&format!("🔍 DEBUG: Unique authors: {}", authors.len()));
         let with_attachments = final_messages.iter().filter(|m| m.attachment.is_some()).count();
-        println!("🔍 DEBUG: Messages with attachments: {}", with_attachments);
-        println!("🔍 DEBUG: =====================================");
+        log.write(&format!("🔍 DEBUG: Messages with attachments: {}", with_attachments));
+        log.write("🔍 DEBUG: =====================================");
     }
 
     final_messages
 }
 
+/// Parses and array of raw messages into an array of structured objects.
+///
+/// Messages that can't be parsed are handled according to the
+/// `on_parse_error` option: skipped, or returned as an error. The dates are
+/// read with the date parser of the options if set, see
+/// `ParseStringOptions::date_time_parser`.
+pub fn parse_messages(messages: &[RawMessage], options: &ParseStringOptions) -> Result<Vec<Message>, ParseError> {
+    if let Some(date_parser) = options_date_parser(options) {
+        return Ok(build_messages_with(prepare_messages_with(messages, &date_parser, options)?, options));
+    }
+    let (parsed, days_first) = prepare_messages(messages, options)?;
+    Ok(build_messages(&parsed, days_first, options))
+}
+
+/// Same as [`parse_messages`] but also returns the non-fatal problems found
+/// along the way.
+///
+/// Messages that can't be parsed are always skipped, each one producing a
+/// [`ParseWarning::SkippedMessage`]. Messages with impossible dates are kept
+/// as in [`parse_messages`], each one producing a [`ParseWarning::InvalidDate`].
+/// With the date parser of the options, the order of the date components and
+/// the validity of the dates are up to the parser, so only messages are
+/// reported.
+pub fn parse_messages_with_warnings(messages: &[RawMessage], options: &ParseStringOptions) -> (Vec<Message>, Vec<ParseWarning>) {
+    let log = DebugLog::new(options);
+    let mut warnings = Vec::new();
+
+    if let Some(date_parser) = options_date_parser(options) {
+        let parsed = map_items(messages, options, |_, obj| extract_parts_with(obj, &date_parser, options))
+            .into_iter()
+            .filter_map(|result| match result {
+                Ok(parts) => Some(parts),
+                Err(ParseError::MalformedMessage(msg)) => {
+                    warnings.push(ParseWarning::SkippedMessage(msg));
+                    None
+                }
+                Err(_) => None,
+            })
+            .collect();
+        return (build_messages_with(parsed, options), warnings);
+    }
+
+    // The raw messages that could be parsed, to locate the invalid dates
+    let mut kept: Vec<&RawMessage> = Vec::new();
+    let parsed: Vec<MessageParts> = map_items(messages, options, |msg_idx, obj| extract_message_parts(msg_idx, obj, options))
+        .into_iter()
+        .zip(messages)
+        .filter_map(|(result, obj)| match result {
+            Ok(parts) => {
+                kept.push(obj);
+                Some(parts)
+            }
+            Err(ParseError::MalformedMessage(msg)) => {
+                warnings.push(ParseWarning::SkippedMessage(msg));
+                None
+            }
+            Err(_) => None,
+        })
+        .collect();
+    let days_first = options
+        .days_first
+        .or_else(|| detect_days_first(parsed.iter().map(|(date, _, _, _, _)| date.as_str()), log));
+    if days_first.is_none() && !parsed.is_empty() {
+        warnings.push(ParseWarning::DefaultedDateOrder);
+    }
+    let days_first = days_first.unwrap_or(options.preferred_order.days_first());
+    warnings.extend(
+        parsed
+            .iter()
+            .zip(kept)
+            .filter(|((date, time, ampm, _, _), _)| !is_valid_datetime(date, time, ampm.as_deref(), days_first, options.two_digit_year_cutoff))
+            .map(|(_, obj)| {
+                ParseWarning::InvalidDate(ParseLineError {
+                    line_number: obj.line_start,
+                    raw_line: obj.msg.lines().next().unwrap_or("").to_string(),
+                    reason: ParseLineErrorReason::InvalidDate,
+                })
+            }),
+    );
+
+    (build_messages(&parsed, days_first, options), warnings)
+}
+
+/// Parses an array of raw messages into `(timestamp, author, message)` tuples,
+/// where `timestamp` is the number of seconds since the Unix epoch (UTC).
+///
+/// Attachments are never parsed by this function.
+pub fn parse_messages_epoch(messages: &[RawMessage], options: &ParseStringOptions) -> Result<Vec<(i64, Option<String>, String)>, ParseError> {
+    let log = DebugLog::new(options);
+    let mut epochs = match options_date_parser(options) {
+        Some(date_parser) => prepare_messages_with(messages, &date_parser, options)?
+            .into_iter()
+            .map(|(date, author, message)| (date.and_utc().timestamp(), author, message))
+            .collect(),
+        None => {
+            let (parsed, days_first) = prepare_messages(messages, options)?;
+            map_items(&parsed, options, |_, (date, time, ampm, author, message)| {
+                let naive_dt = build_datetime(date, time, ampm.as_deref(), days_first, options.two_digit_year_cutoff, log);
+                (naive_dt.and_utc().timestamp(), author.clone(), message.clone())
+            })
+        }
+    };
+    if options.drop_empty {
+        epochs.retain(|(_, _, message)| !message.is_empty());
+    }
+    Ok(epochs)
+}
+
+/// Parses an array of raw messages keeping only the ones sent strictly after
+/// `since`.
+///
+/// Only the date and time are extracted from the earlier messages, their author
+/// and body are never processed. The order of the date components is detected
+/// on all the messages (unless specified in the options), so the result is
+/// consistent with filtering the output of [`parse_messages`].
+pub fn parse_messages_since(messages: &[RawMessage], options: &ParseStringOptions, since: DateTime<Utc>) -> Result<Vec<Message>, ParseError> {
+    let log = DebugLog::new(options);
+    if let Some(date_parser) = options_date_parser(options) {
+        let dates = map_items(messages, options, |_, obj| parse_date_with(obj, &date_parser).map(|(date, _)| (obj, date)));
+        let since = since.naive_utc();
+        let recent: Vec<&RawMessage> = apply_error_policy(dates, options)?
+            .into_iter()
+            .filter(|(_, date)| *date > since)
+            .map(|(obj, _)| obj)
+            .collect();
+        let parsed = map_items(&recent, options, |_, obj| extract_parts_with(obj, &date_parser, options));
+        return Ok(build_messages_with(apply_error_policy(parsed, options)?, options));
+    }
+    let headers = map_items(messages, options, |_, obj| {
+        extract_header(&obj.msg, options)
+            .map(|header| (obj, header))
+            .ok_or_else(|| ParseError::MalformedMessage(obj.msg.clone()))
+    });
+    let headers: Vec<(&RawMessage, HeaderParts)> = apply_error_policy(headers, options)?;
+    let days_first = options
+        .days_first
+        .or_else(|| detect_days_first(headers.iter().map(|(_, (date, _, _))| *date), log))
+        .unwrap_or(options.preferred_order.days_first());
+
+    let since = since.naive_utc();
+    let recent: Vec<&RawMessage> = headers
+        .into_iter()
+        .filter(|(_, (date, time, ampm))| build_datetime(date, time, *ampm, days_first, options.two_digit_year_cutoff, DebugLog::stdout(false)) > since)
+        .map(|(obj, _)| obj)
+        .collect();
+
+    let parsed = map_items(&recent, options, |msg_idx, obj| extract_message_parts(msg_idx, obj, options));
+    let parsed = apply_error_policy(parsed, options)?;
+    Ok(build_messages(&parsed, days_first, options))
+}
+
+/// A [`DateTimeParser`] for dates with a written month, such as
+/// `7. Januar 2023 14:30` or `7 de enero de 2023, 14:30`, found in the exports
+/// of some locales.
+///
+/// Month names are looked up without regard to case. Lines with a month name
+/// that is not in the map, or with an impossible date or time such as
+/// `31. Februar`, don't start a message.
+#[derive(Debug, Clone)]
+pub struct MonthNameDateTimeParser {
+    month_names: HashMap<String, u32>,
+    two_digit_year_cutoff: u8,
+}
+
+impl MonthNameDateTimeParser {
+    /// Creates a parser recognizing the month names of `month_names`, see
+    /// `datetime::month_map_for_locale`.
+    pub fn new(month_names: &HashMap<String, u32>, two_digit_year_cutoff: u8) -> Self {
+        MonthNameDateTimeParser {
+            month_names: month_names.iter().map(|(name, month)| (name.to_lowercase(), *month)).collect(),
+            two_digit_year_cutoff,
+        }
+    }
+}
+
+impl DateTimeParser for MonthNameDateTimeParser {
+    fn parse(&self, header: &str) -> Option<(NaiveDateTime, usize)> {
+        let caps = MONTH_NAME_REGEX.captures(header)?;
+        let month = self.month_names.get(&caps[2].to_lowercase())?;
+        let date = format!("{}/{}/{}", &caps[1], month, &caps[3]);
+        let (time, ampm) = (&caps[4], caps.get(5).map(|m| m.as_str()));
+        if !is_valid_datetime(&date, time, ampm, true, self.two_digit_year_cutoff) {
+            return None;
+        }
+        let date = build_datetime(&date, time, ampm, true, self.two_digit_year_cutoff, DebugLog::stdout(false));
+        Some((date, caps.get(0)?.end()))
+    }
+}
+
+/// The date parser replacing the built-in date parsing, see
+/// [`options_date_parser`].
+enum OptionsDateParser<'a> {
+    /// The `date_time_parser` of the options.
+    Custom(&'a dyn DateTimeParser),
+    /// A parser for the `locale_month_names` of the options.
+    MonthNames(MonthNameDateTimeParser),
+}
+
+impl DateTimeParser for OptionsDateParser<'_> {
+    fn parse(&self, header: &str) -> Option<(NaiveDateTime, usize)> {
+        match self {
+            OptionsDateParser::Custom(parser) => parser.parse(header),
+            OptionsDateParser::MonthNames(parser) => parser.parse(header),
+        }
+    }
+}
+
+/// The date parser set in the options: `date_time_parser`, otherwise one for
+/// the `locale_month_names`. `None` if neither is set and the built-in date
+/// parsing is used.
+fn options_date_parser(options: &ParseStringOptions) -> Option<OptionsDateParser<'_>> {
+    if let Some(parser) = &options.date_time_parser {
+        return Some(OptionsDateParser::Custom(parser.as_ref()));
+    }
+    let month_names = options.locale_month_names.as_ref()?;
+    Some(OptionsDateParser::MonthNames(MonthNameDateTimeParser::new(month_names, options.two_digit_year_cutoff)))
+}
+
+/// The date, optional author and cleaned up body of a message, as extracted
+/// with a [`DateTimeParser`].
+type DatedParts = (NaiveDateTime, Option<String>, String);
+
+/// Finds the date at the start of a raw message with `date_parser`, returning
+/// it with the rest of the first line.
+///
+/// Fails with `ParseError::MalformedMessage` if the parser doesn't recognize
+/// the first line, which can only happen for raw messages built by hand.
+fn parse_date_with<'a>(obj: &'a RawMessage, date_parser: &dyn DateTimeParser) -> Result<(NaiveDateTime, &'a str), ParseError> {
+    let header = obj.msg.split('\n').next().unwrap_or("");
+    let (date, consumed) = date_parser.parse(header).ok_or_else(|| ParseError::MalformedMessage(obj.msg.clone()))?;
+    Ok((date, header.get(consumed..).unwrap_or("")))
+}
+
+/// Extracts the components of a raw message with `date_parser`.
+///
+/// Only the line with the date can have an author, the continuation lines are
+/// part of the body whatever they contain. Fails with
+/// `ParseError::MalformedMessage` if the first line has no date, or no author
+/// for a user message.
+fn extract_parts_with(obj: &RawMessage, date_parser: &dyn DateTimeParser, options: &ParseStringOptions) -> Result<DatedParts, ParseError> {
+    let (date, rest) = parse_date_with(obj, date_parser)?;
+    let (author, body) = if obj.system {
+        (None, rest)
+    } else {
+        let (author, body) = split_author(rest, &options.known_authors).ok_or_else(|| ParseError::MalformedMessage(obj.msg.clone()))?;
+        (Some(strip_directional_marks(author).trim().to_string()), body)
+    };
+    let body = match obj.msg.split_once('\n') {
+        Some((_, continuation)) => Cow::Owned(format!("{}\n{}", body, continuation)),
+        None => Cow::Borrowed(body),
+    };
+    Ok((date, author, strip_directional_marks(&body).trim().to_string()))
+}
+
+/// Extracts the components of every raw message with `date_parser`, applying
+/// the error policy of the options.
+fn prepare_messages_with(messages: &[RawMessage], date_parser: &dyn DateTimeParser, options: &ParseStringOptions) -> Result<Vec<DatedParts>, ParseError> {
+    let parsed = map_items(messages, options, |_, obj| extract_parts_with(obj, date_parser, options));
+    apply_error_policy(parsed, options)
+}
+
+/// Turns the components extracted with a [`DateTimeParser`] into structured
+/// objects.
+fn build_messages_with(parsed: Vec<DatedParts>, options: &ParseStringOptions) -> Vec<Message> {
+    let mut messages = map_items(&parsed, options, |_, (date, author, body)| finish_message(*date, author.clone(), body, options));
+    if options.drop_empty {
+        messages.retain(|m| !m.message.is_empty() || m.attachment.is_some());
+    }
+    messages
+}
+
+/// Parses lines into messages using a custom [`DateTimeParser`] to find the
+/// start of each message and its date.
+///
+/// Same as aggregating the lines and parsing the messages with `date_parser`
+/// set as the `date_time_parser` of the options. The rest of the line after
+/// the date is split into author and body the same way the built-in parser
+/// does: lines with an `Author: ` prefix are user messages, the others system
+/// messages. Lines the parser doesn't recognize are continuations of the
+/// previous message, and dropped if there is none.
+pub fn parse_lines_with<'a>(lines: impl IntoIterator<Item = &'a str>, date_parser: &dyn DateTimeParser, options: &ParseStringOptions) -> Result<Vec<Message>, ParseError> {
+    let (messages, _) = aggregate_lines_with_parser(lines, date_parser, options, &mut ParseStats::default());
+    Ok(build_messages_with(prepare_messages_with(&messages, date_parser, options)?, options))
+}
+
+/// Finds the dates of the earliest and latest messages in a sequence of lines,
+/// without extracting authors or bodies.
+///
+/// Output is `None` if no line starts a message.
+pub fn find_date_range<'a>(lines: impl IntoIterator<Item = &'a str>, options: &ParseStringOptions) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    if let Some(date_parser) = options_date_parser(options) {
+        return date_range(lines.into_iter().filter_map(|line| date_parser.parse(line)).map(|(date, _)| date));
+    }
+    let headers: Vec<HeaderParts> = lines.into_iter().filter_map(|line| extract_header(line, options)).collect();
+    if headers.is_empty() {
+        return None;
+    }
+
+    let days_first = options
+        .days_first
+        .or_else(|| detect_days_first(headers.iter().map(|(date, _, _)| *date), DebugLog::new(options)))
+        .unwrap_or(options.preferred_order.days_first());
+    date_range(headers.iter().map(|(date, time, ampm)| build_datetime(date, time, *ampm, days_first, options.two_digit_year_cutoff, DebugLog::stdout(false))))
+}
+
+/// The earliest and latest of `dates`, or `None` if there are none.
+fn date_range(dates: impl Iterator<Item = NaiveDateTime>) -> Option<(DateTime<Utc>, DateTime<Utc>)> {
+    dates.map(|date| date.and_utc()).fold(None, |range, date| match range {
+        Some((earliest, latest)) => Some((std::cmp::min(earliest, date), std::cmp::max(latest, date))),
+        None => Some((date, date)),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::models::RawMessage;
+    use crate::models::{DateOrder, RawMessage};
     use chrono::{Datelike, TimeZone, Timelike, Utc};
 
     #[test]
     fn test_make_array_of_messages_multiline() {
         let multiline_message = vec!["23/06/2018, 01:55 p.m. - Loris: one", "two"];
         assert_eq!(
-            make_array_of_messages(&multiline_message)[0].msg,
+            make_array_of_messages_slice(&multiline_message)[0].msg,
             "23/06/2018, 01:55 p.m. - Loris: one\ntwo"
         );
     }
@@ -337,10 +1084,10 @@ mod tests {
             "This is another line",
         ];
 
-        assert!(!make_array_of_messages(&multiline_message)[0].system);
-        assert!(!make_array_of_messages(&empty_message)[0].system);
-        assert!(make_array_of_messages(&multiline_system_message)[0].system);
-        assert!(make_array_of_messages(&system_message)[0].system);
+        assert!(!make_array_of_messages_slice(&multiline_message)[0].system);
+        assert!(!make_array_of_messages_slice(&empty_message)[0].system);
+        assert!(make_array_of_messages_slice(&multiline_system_message)[0].system);
+        assert!(make_array_of_messages_slice(&system_message)[0].system);
     }
 
     #[test]
@@ -351,18 +1098,218 @@ mod tests {
             "2016-04-29 10:30:00",
         ];
         assert_eq!(
-            make_array_of_messages(&multiline_message)[0].msg,
+            make_array_of_messages_slice(&multiline_message)[0].msg,
             "23/06/2018, 01:55 p.m. - Loris: one\ntwo\n2016-04-29 10:30:00"
         );
     }
 
+    #[test]
+    fn test_make_array_of_messages_with_errors() {
+        let lines = vec!["garbage", "23/06/2018, 01:55 p.m. - Loris: one", "two"];
+        let (messages, errors) = make_array_of_messages_with_errors(&lines, false);
+
+        assert_eq!(messages.len(), 1);
+        assert_eq!(
+            errors,
+            vec![ParseLineError {
+                line_number: 1,
+                raw_line: "garbage".to_string(),
+                reason: ParseLineErrorReason::NoPrecedingMessage,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        let inputs = [
+            "",
+            "\n",
+            "one",
+            "one\ntwo\n",
+            "one\r\ntwo\r\n\r\nthree",
+            "\u{FEFF}23/06/2018, 01:55 - Luke: Hey!\nsecond line",
+        ];
+        for input in inputs {
+            let expected: Vec<&str> = input
+                .strip_prefix('\u{FEFF}')
+                .unwrap_or(input)
+                .split('\n')
+                .map(|l| l.trim_end_matches('\r'))
+                .collect();
+            assert_eq!(Lines::new(input).collect::<Vec<_>>(), expected);
+        }
+    }
+
+    #[test]
+    fn test_aggregate_lines_matches_slices() {
+        let input = "Exported chat\r\n23/06/2018, 01:55 - Luke: one\r\ntwo\r\n23/06/2018, 01:56 - Luke added Leia\r\n";
+        let lines: Vec<&str> = input.split('\n').map(|l| l.trim_end_matches('\r')).collect();
+
+        assert_eq!(aggregate_lines(Lines::new(input), false), make_array_of_messages_with_errors(&lines, false));
+    }
+
+    #[test]
+    fn test_make_array_of_messages_line_numbers() {
+        let lines = ["Exported chat", "23/06/2018, 01:55 - Luke: one", "two", "three", "23/06/2018, 01:56 - Luke added Leia", "23/06/2018, 01:57 - Leia: four"];
+        let messages = make_array_of_messages_slice(&lines);
+        let spans: Vec<(usize, usize)> = messages.iter().map(|m| (m.line_start, m.line_end)).collect();
+
+        assert_eq!(spans, [(2, 4), (5, 5), (6, 6)]);
+    }
+
+    #[test]
+    fn test_aggregate_lines_with_stats() {
+        let lines = [
+            "Messages to this group are now secured",
+            "23/06/2018, 01:55 - Luke created group \"Rebels\"",
+            "23/06/2018, 01:55 - Luke: Hey!",
+            "second line",
+            "third line",
+            "23/06/2018, 01:56 - Leia: Hi",
+        ];
+        let (messages, errors, stats) = aggregate_lines_with_stats(lines, &ParseStringOptions::default());
+
+        assert_eq!(
+            stats,
+            ParseStats {
+                total_lines: 6,
+                message_starts: 2,
+                continuation_lines: 2,
+                system_message_starts: 1,
+                discarded_lines: 1,
+            }
+        );
+        assert_eq!(messages.len(), stats.message_starts + stats.system_message_starts);
+        assert_eq!(errors.len(), stats.discarded_lines);
+    }
+
+    #[test]
+    fn test_make_array_of_messages_iterator_matches_slice() {
+        let input = "23/06/2018, 01:55 - Luke: one\ntwo\n23/06/2018, 01:56 - Luke added Leia\nthree";
+        let lines: Vec<&str> = input.lines().collect();
+        let from_slice = make_array_of_messages_slice(&lines);
+
+        assert_eq!(from_slice.len(), 2);
+        assert_eq!(make_array_of_messages(input.lines()), from_slice);
+        assert_eq!(make_array_of_messages(Lines::new(input)), from_slice);
+        assert_eq!(make_array_of_messages_with_debug(lines.iter().copied(), false), from_slice);
+    }
+
+    #[test]
+    fn test_count_messages() {
+        let lines = vec![
+            "06/03/2017, 00:45 - You created group \"Test\"",
+            "23/06/2018, 01:55 p.m. - Loris: one",
+            "two",
+            "2016-04-29 10:30:00",
+            "03/02/17, 18:42 - Luke: ",
+        ];
+        assert_eq!(count_messages(lines.iter().copied()), make_array_of_messages_slice(&lines).len());
+        assert_eq!(count_messages(lines.iter().copied()), 3);
+    }
+
+    #[test]
+    fn test_collect_authors() {
+        let lines = vec![
+            "06/03/2017, 00:45 - You created group \"Test\"",
+            "23/06/2018, 01:55 p.m. - Loris: one",
+            "two: not an author",
+            "23/06/2018, 01:56 p.m. - \u{200E}Luke: three",
+            "23/06/2018, 01:57 p.m. - Loris: four",
+        ];
+        assert_eq!(collect_authors(lines), vec!["Loris".to_string(), "Luke".to_string()]);
+    }
+
+    #[test]
+    fn test_find_date_range() {
+        let lines = [
+            "13/06/2018, 10:00 - a: late",
+            "01/06/2018, 09:30 - a: early",
+            "02/06/2018, 10:00 - b: middle",
+            "continuation 03/06/2099, 10:00",
+        ];
+        let range = find_date_range(lines, &ParseStringOptions::default());
+
+        assert_eq!(
+            range,
+            Some((
+                Utc.with_ymd_and_hms(2018, 6, 1, 9, 30, 0).unwrap(),
+                Utc.with_ymd_and_hms(2018, 6, 13, 10, 0, 0).unwrap()
+            ))
+        );
+        assert_eq!(find_date_range(["not a message"], &ParseStringOptions::default()), None);
+    }
+
+    #[test]
+    fn test_parse_messages_since() {
+        let messages = make_array_of_messages_slice(&[
+            "01/06/2018, 10:00 - a: first",
+            "02/06/2018, 10:00 - b: second",
+            "multiline",
+            "13/06/2018, 10:00 - a: third",
+        ]);
+        let since = Utc.with_ymd_and_hms(2018, 6, 2, 10, 0, 0).unwrap();
+        let parsed = parse_messages_since(&messages, &ParseStringOptions::default(), since).unwrap();
+
+        // Day-first is detected thanks to the earlier, skipped messages too
+        assert_eq!(parsed.len(), 1);
+        assert_eq!(parsed[0].message, "third");
+        assert_eq!(parsed[0].date, Utc.with_ymd_and_hms(2018, 6, 13, 10, 0, 0).unwrap());
+    }
+
+    #[test]
+    #[cfg(feature = "parallel")]
+    fn test_parse_messages_thread_pool() {
+        let lines: Vec<String> = (0..200).map(|i| format!("{}/06/2018, 10:{:02} - a{}: message {}", i % 28 + 1, i % 60, i % 3, i)).collect();
+        let lines: Vec<&str> = lines.iter().map(|l| l.as_str()).collect();
+        let messages = make_array_of_messages_slice(&lines);
+
+        let global = parse_messages(&messages, &ParseStringOptions::default()).unwrap();
+        let single_thread = parse_messages(&messages, &ParseStringOptions::with_thread_count(1)).unwrap();
+
+        assert_eq!(single_thread.len(), 200);
+        assert_eq!(single_thread, global);
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_parse_messages_parallel_threshold() {
+        let lines = [
+            "3/6/18, 1:55 p.m. - a: m",
+            "03-06-2018, 01.55 PM - a: m",
+            "13.06.18 21.25.15: a: m",
+            "[06.13.18 21:25:15] a: m",
+            "13.6.2018 klo 21.25.15 - a: m",
+            "13/06/2018, 21:25 - a created group \"g\"",
+            "13/06/2018, 21:26 - b: IMG-20180613-WA0001.jpg (file attached)",
+            "second line",
+            "31/02/2018, 21:27 - c: invalid date",
+        ];
+        let messages = make_array_of_messages_slice(&lines);
+        let parse = |threshold| {
+            let options = ParseStringOptions {
+                parse_attachments: true,
+                parallel_threshold: Some(threshold),
+                ..Default::default()
+            };
+            (parse_messages(&messages, &options).unwrap(), parse_messages_with_warnings(&messages, &options))
+        };
+
+        let (sequential, sequential_warnings) = parse(usize::MAX);
+        let (parallel, parallel_warnings) = parse(0);
+        assert_eq!(sequential.len(), 8);
+        assert_eq!(sequential, parallel);
+        assert_eq!(sequential_warnings, parallel_warnings);
+    }
+
     #[test]
     fn test_parse_messages_normal() {
         let messages = vec![RawMessage {
             system: false,
             msg: "23/06/2018, 01:55 a.m. - Luke: Hey!".to_string(),
+            ..Default::default()
         }];
-        let parsed = parse_messages(&messages, &ParseStringOptions::default());
+        let parsed = parse_messages(&messages, &ParseStringOptions::default()).unwrap();
 
         assert_eq!(parsed[0].date.year(), 2018);
         assert_eq!(parsed[0].date.month(), 6);
@@ -379,8 +1326,9 @@ mod tests {
         let messages = vec![RawMessage {
             system: true,
             msg: "06/03/2017, 00:45 - You created group \"Test\"".to_string(),
+            ..Default::default()
         }];
-        let parsed = parse_messages(&messages, &ParseStringOptions::default());
+        let parsed = parse_messages(&messages, &ParseStringOptions::default()).unwrap();
 
         assert_eq!(parsed[0].date.year(), 2017);
         assert_eq!(parsed[0].date.month(), 3);
@@ -397,64 +1345,76 @@ mod tests {
         let format1 = RawMessage {
             system: false,
             msg: "3/6/18, 1:55 p.m. - a: m".to_string(),
+            ..Default::default()
         };
         let format2 = RawMessage {
             system: false,
             msg: "03-06-2018, 01.55 PM - a: m".to_string(),
+            ..Default::default()
         };
         let format3 = RawMessage {
             system: false,
             msg: "13.06.18 21.25.15: a: m".to_string(),
+            ..Default::default()
         };
         let format4 = RawMessage {
             system: false,
             msg: "[06.13.18 21:25:15] a: m".to_string(),
+            ..Default::default()
         };
         let format5 = RawMessage {
             system: false,
             msg: "13.6.2018 klo 21.25.15 - a: m".to_string(),
+            ..Default::default()
         };
         let format6 = RawMessage {
             system: false,
             msg: "13. 6. 2018. 21:25:15 a: m".to_string(),
+            ..Default::default()
         };
         let format7 = RawMessage {
             system: false,
             msg: "[3/6/18 1:55:00 p. m.] a: m".to_string(),
+            ..Default::default()
         };
         let format8 = RawMessage {
             system: false,
             msg: "\u{200E}[3/6/18 1:55:00 p. m.] a: m".to_string(),
+            ..Default::default()
         };
         let format9 = RawMessage {
             system: false,
             msg: "[2018/06/13, 21:25:15] a: m".to_string(),
+            ..Default::default()
         };
         let format10 = RawMessage {
             system: false,
             msg: "[06/2018/13, 21:25:15] a: m".to_string(),
+            ..Default::default()
         };
         let format11 = RawMessage {
             system: false,
             msg: "3/6/2018 1:55 p. m. - a: m".to_string(),
+            ..Default::default()
         };
         let format12 = RawMessage {
             system: false,
             msg: "3/6/18, 1:55\u{202F}PM - a: m".to_string(),
+            ..Default::default()
         };
 
-        let parsed1 = parse_messages(&vec![format1], &ParseStringOptions::default());
-        let parsed2 = parse_messages(&vec![format2], &ParseStringOptions::default());
-        let parsed3 = parse_messages(&vec![format3], &ParseStringOptions::default());
-        let parsed4 = parse_messages(&vec![format4], &ParseStringOptions::default());
-        let parsed5 = parse_messages(&vec![format5], &ParseStringOptions::default());
-        let parsed6 = parse_messages(&vec![format6], &ParseStringOptions::default());
-        let parsed7 = parse_messages(&vec![format7], &ParseStringOptions::default());
-        let parsed8 = parse_messages(&vec![format8], &ParseStringOptions::default());
-        let parsed9 = parse_messages(&vec![format9], &ParseStringOptions::default());
-        let parsed10 = parse_messages(&vec![format10], &ParseStringOptions::default());
-        let parsed11 = parse_messages(&vec![format11], &ParseStringOptions::default());
-        let parsed12 = parse_messages(&vec![format12], &ParseStringOptions::default());
+        let parsed1 = parse_messages(&[format1], &ParseStringOptions::default()).unwrap();
+        let parsed2 = parse_messages(&[format2], &ParseStringOptions::default()).unwrap();
+        let parsed3 = parse_messages(&[format3], &ParseStringOptions::default()).unwrap();
+        let parsed4 = parse_messages(&[format4], &ParseStringOptions::default()).unwrap();
+        let parsed5 = parse_messages(&[format5], &ParseStringOptions::default()).unwrap();
+        let parsed6 = parse_messages(&[format6], &ParseStringOptions::default()).unwrap();
+        let parsed7 = parse_messages(&[format7], &ParseStringOptions::default()).unwrap();
+        let parsed8 = parse_messages(&[format8], &ParseStringOptions::default()).unwrap();
+        let parsed9 = parse_messages(&[format9], &ParseStringOptions::default()).unwrap();
+        let parsed10 = parse_messages(&[format10], &ParseStringOptions::default()).unwrap();
+        let parsed11 = parse_messages(&[format11], &ParseStringOptions::default()).unwrap();
+        let parsed12 = parse_messages(&[format12], &ParseStringOptions::default()).unwrap();
 
         let expected1 = Utc.with_ymd_and_hms(2018, 6, 3, 13, 55, 0).unwrap();
         let expected2 = Utc.with_ymd_and_hms(2018, 6, 13, 21, 25, 15).unwrap();
@@ -478,26 +1438,83 @@ mod tests {
         let messages = vec![RawMessage {
             system: false,
             msg: "3/6/18, 1:55 p.m. - a: m".to_string(),
+            ..Default::default()
         }];
         let parsed_day_first = parse_messages(
             &messages,
             &ParseStringOptions {
-                days_first: Some(true),
+                days_first: Some(true),
+                ..Default::default()
+            },
+        ).unwrap();
+        let parsed_month_first = parse_messages(
+            &messages,
+            &ParseStringOptions {
+                days_first: Some(false),
+                ..Default::default()
+            },
+        ).unwrap();
+
+        assert_eq!(parsed_day_first[0].date.day(), 3);
+        assert_eq!(parsed_day_first[0].date.month(), 6);
+        assert_eq!(parsed_month_first[0].date.day(), 6);
+        assert_eq!(parsed_month_first[0].date.month(), 3);
+    }
+
+    #[test]
+    fn test_parse_messages_preferred_order() {
+        let messages = vec![
+            RawMessage {
+                system: false,
+                msg: "3/6/18, 1:55 p.m. - a: m".to_string(),
+                ..Default::default()
+            },
+            RawMessage {
+                system: false,
+                msg: "3/6/18, 1:56 p.m. - b: m".to_string(),
+                ..Default::default()
+            },
+        ];
+        let parsed_default = parse_messages(&messages, &ParseStringOptions::default()).unwrap();
+        let parsed_month_first = parse_messages(
+            &messages,
+            &ParseStringOptions {
+                preferred_order: DateOrder::MonthDayYear,
                 ..Default::default()
             },
-        );
-        let parsed_month_first = parse_messages(
+        ).unwrap();
+        let parsed_explicit = parse_messages(
             &messages,
             &ParseStringOptions {
-                days_first: Some(false),
+                days_first: Some(true),
+                preferred_order: DateOrder::MonthDayYear,
                 ..Default::default()
             },
-        );
+        ).unwrap();
 
-        assert_eq!(parsed_day_first[0].date.day(), 3);
-        assert_eq!(parsed_day_first[0].date.month(), 6);
-        assert_eq!(parsed_month_first[0].date.day(), 6);
-        assert_eq!(parsed_month_first[0].date.month(), 3);
+        assert_eq!((parsed_default[1].date.day(), parsed_default[1].date.month()), (3, 6));
+        assert_eq!((parsed_month_first[1].date.day(), parsed_month_first[1].date.month()), (6, 3));
+        assert_eq!((parsed_explicit[1].date.day(), parsed_explicit[1].date.month()), (3, 6));
+    }
+
+    #[test]
+    fn test_parse_messages_single_ambiguous_message() {
+        let messages = vec![RawMessage {
+            system: false,
+            msg: "3/6/18, 1:55 p.m. - a: m".to_string(),
+            ..Default::default()
+        }];
+        let parse = |preferred_order| {
+            let options = ParseStringOptions {
+                preferred_order,
+                ..Default::default()
+            };
+            let parsed = parse_messages(&messages, &options).unwrap();
+            (parsed[0].date.day(), parsed[0].date.month())
+        };
+
+        assert_eq!(parse(DateOrder::default()), (3, 6));
+        assert_eq!(parse(DateOrder::MonthDayYear), (6, 3));
     }
 
     #[test]
@@ -510,22 +1527,27 @@ mod tests {
             RawMessage {
                 system: false,
                 msg: format1.to_string(),
+                ..Default::default()
             },
             RawMessage {
                 system: false,
                 msg: "3/6/18, 1:55 p.m. - a: m".to_string(),
+                ..Default::default()
             },
             RawMessage {
                 system: false,
                 msg: format2.to_string(),
+                ..Default::default()
             },
             RawMessage {
                 system: false,
                 msg: format3.to_string(),
+                ..Default::default()
             },
             RawMessage {
                 system: false,
                 msg: format4.to_string(),
+                ..Default::default()
             },
         ];
 
@@ -535,14 +1557,14 @@ mod tests {
                 parse_attachments: false,
                 ..Default::default()
             },
-        );
+        ).unwrap();
         let parsed_with_attachments = parse_messages(
             &messages,
             &ParseStringOptions {
                 parse_attachments: true,
                 ..Default::default()
             },
-        );
+        ).unwrap();
 
         assert_eq!(
             parsed_with_attachments[0]
@@ -580,6 +1602,414 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_parse_messages_error_policy() {
+        let messages = vec![
+            RawMessage {
+                system: false,
+                msg: "23/06/2018, 01:55 - Luke: Hey!".to_string(),
+                ..Default::default()
+            },
+            RawMessage {
+                system: false,
+                msg: "this is not a message".to_string(),
+                ..Default::default()
+            },
+            RawMessage {
+                system: true,
+                msg: "\u{200E}\u{200F}".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let skipped = parse_messages(
+            &messages,
+            &ParseStringOptions {
+                on_parse_error: ErrorPolicy::Skip,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(skipped.len(), 1);
+        assert_eq!(skipped[0].message, "Hey!");
+
+        let failed = parse_messages(
+            &messages,
+            &ParseStringOptions {
+                on_parse_error: ErrorPolicy::Fail,
+                ..Default::default()
+            },
+        );
+        assert!(matches!(failed, Err(ParseError::MalformedMessage(msg)) if msg == "this is not a message"));
+
+        let since = Utc.with_ymd_and_hms(2000, 1, 1, 0, 0, 0).unwrap();
+        assert_eq!(parse_messages_since(&messages, &ParseStringOptions::default(), since).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_parse_messages_invalid_dates() {
+        let messages: Vec<RawMessage> = ["30/02/2018", "13/13/2018", "00/01/2018"]
+            .iter()
+            .map(|date| RawMessage {
+                system: false,
+                msg: format!("{}, 25:70 - Luke: Hey!", date),
+                ..Default::default()
+            })
+            .collect();
+        let fallback = Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).unwrap();
+
+        for on_parse_error in [ErrorPolicy::Skip, ErrorPolicy::Fail] {
+            let options = ParseStringOptions {
+                days_first: Some(true),
+                on_parse_error,
+                ..Default::default()
+            };
+            let parsed = parse_messages(&messages, &options).unwrap();
+
+            assert_eq!(parsed.len(), 3);
+            assert!(parsed.iter().all(|m| m.date == fallback && m.message == "Hey!"));
+        }
+
+        let options = ParseStringOptions {
+            days_first: Some(true),
+            ..Default::default()
+        };
+        let (parsed, warnings) = parse_messages_with_warnings(&messages, &options);
+        assert_eq!(parsed.len(), 3);
+        assert_eq!(warnings.len(), 3);
+        assert!(matches!(&warnings[0], ParseWarning::InvalidDate(err) if err.raw_line == "30/02/2018, 25:70 - Luke: Hey!"));
+    }
+
+    #[test]
+    fn test_parse_messages_unknown_digits() {
+        // Mongolian digits match the header regex but are not normalized
+        let messages = vec![RawMessage {
+            system: false,
+            msg: "\u{1811}/\u{1811}/\u{1811}\u{1818}, \u{1811}:\u{1815}\u{1815} p.m. - Luke: Hey!".to_string(),
+            ..Default::default()
+        }];
+        let parsed = parse_messages(&messages, &ParseStringOptions::default()).unwrap();
+
+        assert_eq!(parsed[0].date, Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).unwrap());
+        assert_eq!(parsed[0].message, "Hey!");
+    }
+
+    #[test]
+    fn test_parse_messages_trailing_attachment() {
+        let messages = vec![
+            RawMessage {
+                system: false,
+                msg: "3/6/18, 1:55 p.m. - a: Check this out IMG-1.jpg (file attached)".to_string(),
+                ..Default::default()
+            },
+            RawMessage {
+                system: false,
+                msg: "3/6/18, 1:55 p.m. - a: Look \u{200E}<attached: 00000042-PHOTO-2020-06-07-15-13-20.jpg>".to_string(),
+                ..Default::default()
+            },
+            RawMessage {
+                system: false,
+                msg: "3/6/18, 1:55 p.m. - a: IMG-2.jpg (file attached)".to_string(),
+                ..Default::default()
+            },
+            RawMessage {
+                system: false,
+                msg: "3/6/18, 1:55 p.m. - a: just text (with parentheses)".to_string(),
+                ..Default::default()
+            },
+        ];
+
+        let anchored = parse_messages(
+            &messages,
+            &ParseStringOptions {
+                parse_attachments: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert!(anchored[0].attachment.is_none());
+        assert!(anchored[1].attachment.is_none());
+        assert_eq!(anchored[2].attachment.as_ref().unwrap().file_name, "IMG-2.jpg");
+
+        let anywhere = parse_messages(
+            &messages,
+            &ParseStringOptions {
+                parse_attachments: true,
+                attachments_anywhere: true,
+                ..Default::default()
+            },
+        )
+        .unwrap();
+        assert_eq!(anywhere[0].attachment.as_ref().unwrap().file_name, "IMG-1.jpg");
+        assert_eq!(anywhere[1].attachment.as_ref().unwrap().file_name, "00000042-PHOTO-2020-06-07-15-13-20.jpg");
+        assert_eq!(anywhere[2].attachment.as_ref().unwrap().file_name, "IMG-2.jpg");
+        assert!(anywhere[3].attachment.is_none());
+    }
+
+    #[test]
+    fn test_parse_messages_starred() {
+        let messages = vec![
+            RawMessage {
+                system: false,
+                msg: "23/06/2018, 01:55 - Luke: Remember this ⭐".to_string(),
+                ..Default::default()
+            },
+            RawMessage {
+                system: false,
+                msg: "23/06/2018, 01:56 - Luke: Not starred".to_string(),
+                ..Default::default()
+            },
+        ];
+        let options = ParseStringOptions {
+            star_markers: vec!["⭐".to_string()],
+            ..Default::default()
+        };
+        let parsed = parse_messages(&messages, &options).unwrap();
+
+        assert!(parsed[0].starred);
+        assert_eq!(parsed[0].message, "Remember this");
+        assert!(!parsed[1].starred);
+        assert_eq!(parsed[1].message, "Not starred");
+
+        let parsed = parse_messages(&messages, &ParseStringOptions::default()).unwrap();
+        assert!(!parsed[0].starred);
+        assert_eq!(parsed[0].message, "Remember this ⭐");
+    }
+
+    #[test]
+    fn test_parse_message_attachment_size() {
+        let attachment = parse_message_attachment("IMG-1.jpg (2.3 MB) (file attached)", false).unwrap();
+        assert_eq!(attachment.file_name, "IMG-1.jpg");
+        assert_eq!(attachment.size, Some(2411725));
+
+        assert_eq!(parse_message_attachment("DOC-1.pdf (512 kB) (file attached)", false).unwrap().size, Some(524288));
+        assert_eq!(parse_message_attachment("PTT-1.opus (1,5 KB) (file attached)", false).unwrap().size, Some(1536));
+        assert_eq!(parse_message_attachment("IMG-1.jpg (file attached)", false).unwrap().size, None);
+        assert_eq!(parse_message_attachment("<attached: 00000042-PHOTO.jpg>", false).unwrap().size, None);
+    }
+
+    #[test]
+    fn test_parse_message_attachment_spaces() {
+        let file_name = |message: &str| parse_message_attachment(message, false).map(|a| a.file_name);
+
+        assert_eq!(file_name("My Photo.jpg (file attached)"), Some("My Photo.jpg".to_string()));
+        assert_eq!(file_name("file with spaces.pdf (file attached)"), Some("file with spaces.pdf".to_string()));
+        assert_eq!(file_name("Luke's photo 2023.jpg (file attached)"), Some("Luke's photo 2023.jpg".to_string()));
+        assert_eq!(file_name("My Photo.jpg  (file attached)"), Some("My Photo.jpg".to_string()));
+        assert_eq!(file_name("IMG-20210428-WA0001.jpg (file attached)"), Some("IMG-20210428-WA0001.jpg".to_string()));
+        assert_eq!(file_name("My Photo.jpg (2.3 MB) (file attached)"), Some("My Photo.jpg".to_string()));
+        assert_eq!(file_name("just text (with parentheses)"), None);
+        assert_eq!(file_name("Check this out IMG-1.jpg (file attached)"), None);
+        assert_eq!(file_name("Notes 00000042-PHOTO-2020-06-07-15-13-20.jpg <attached>"), None);
+        assert_eq!(file_name("I paid 3.50 (cash)"), None);
+        assert_eq!(file_name("Meet at the cafe.Ok (lol)"), None);
+        assert_eq!(file_name("hello there\nsee photo.jpg (file attached)"), None);
+        assert_eq!(file_name("<3 love: you>"), None);
+        assert_eq!(parse_message_attachment("Look at My Photo.jpg (file attached)", true).unwrap().file_name, "Photo.jpg");
+    }
+
+    #[test]
+    fn test_parse_message_attachment_omitted() {
+        let kinds = [("sticker", MediaKind::Sticker), ("audio", MediaKind::Audio), ("image", MediaKind::Image), ("video", MediaKind::Video)];
+        for (media, kind) in kinds {
+            for message in [format!("{} omitted", media), format!("\u{200E}{} omitted", media)] {
+                for anywhere in [false, true] {
+                    let attachment = parse_message_attachment(&message, anywhere);
+                    assert!(attachment.is_some(), "{:?}", message);
+                    let attachment = attachment.unwrap();
+                    assert_eq!((attachment.file_name.as_str(), attachment.kind), (media, Some(kind)));
+                }
+            }
+        }
+        assert_eq!(parse_message_attachment("IMG-1.jpg (file attached)", false).unwrap().kind, None);
+        assert!(parse_message_attachment("the sticker omitted something", false).is_none());
+    }
+
+    #[test]
+    fn test_parse_message_attachment_dots() {
+        let archive = parse_message_attachment("archive.tar.gz (file attached)", false).unwrap();
+        assert_eq!(archive.file_name, "archive.tar.gz");
+        assert_eq!(archive.extension(), Some("gz"));
+
+        let report = parse_message_attachment("report.v2.final.pdf (file attached)", false).unwrap();
+        assert_eq!(report.file_name, "report.v2.final.pdf");
+        assert_eq!(report.extension(), Some("pdf"));
+        assert_eq!(parse_message_attachment("My report v1.2.pdf (120 kB) (file attached)", false).unwrap().file_name, "My report v1.2.pdf");
+        assert_eq!(parse_message_attachment("Look archive.tar.gz (file attached)", true).unwrap().file_name, "archive.tar.gz");
+
+        for message in ["I paid 3.50 (cash)", "See you at 5.30 (maybe)", "Meet at the cafe.Ok (lol)", "...ok (lol)", "...ok (file attached)", "hello\nthere.jpg (lol)"] {
+            assert!(parse_message_attachment(message, false).is_none(), "{:?}", message);
+            assert!(parse_message_attachment(message, true).is_none(), "{:?}", message);
+        }
+        assert!(parse_message_attachment("hello there\nsee photo.v2.jpg (file attached)", false).is_none());
+        assert_eq!(parse_message_attachment("hello there\nsee photo.v2.jpg (file attached)", true).unwrap().file_name, "photo.v2.jpg");
+    }
+
+    #[test]
+    fn test_parse_message_attachment_directional_marks() {
+        let attachment = |message: &str, anywhere| parse_message_attachment(message, anywhere).map(|a| a.file_name);
+
+        assert_eq!(attachment("IMG-1.jpg \u{200E}(file attached)", false), Some("IMG-1.jpg".to_string()));
+        assert_eq!(attachment("\u{200F}IMG-1.jpg\u{200E} (file attached)", false), Some("IMG-1.jpg".to_string()));
+        assert_eq!(attachment("\u{200E}<attached: 00000042-PHOTO.jpg\u{200E}>", false), Some("00000042-PHOTO.jpg".to_string()));
+        assert_eq!(attachment("Look \u{200E}IMG-1.jpg \u{200E}(file attached)", true), Some("IMG-1.jpg".to_string()));
+    }
+
+    #[test]
+    fn test_parse_messages_drop_empty() {
+        let messages = vec![
+            RawMessage {
+                system: false,
+                msg: "23/06/2018, 01:55 - Luke: \u{200E}".to_string(),
+                ..Default::default()
+            },
+            RawMessage {
+                system: false,
+                msg: "23/06/2018, 01:56 - Luke: ⭐".to_string(),
+                ..Default::default()
+            },
+            RawMessage {
+                system: false,
+                msg: "23/06/2018, 01:57 - Luke: \u{200E}<attached: 00000042-PHOTO.jpg>".to_string(),
+                ..Default::default()
+            },
+            RawMessage {
+                system: false,
+                msg: "23/06/2018, 01:58 - Luke: Hey!".to_string(),
+                ..Default::default()
+            },
+        ];
+        let options = ParseStringOptions {
+            drop_empty: true,
+            parse_attachments: true,
+            star_markers: vec!["⭐".to_string()],
+            ..Default::default()
+        };
+        let parsed = parse_messages(&messages, &options).unwrap();
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].attachment.as_ref().unwrap().file_name, "00000042-PHOTO.jpg");
+        assert_eq!(parsed[1].message, "Hey!");
+        assert_eq!(parse_messages(&messages, &ParseStringOptions::default()).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_parse_messages_arabic_indic_digits() {
+        let messages = vec![
+            RawMessage {
+                system: false,
+                msg: "\u{200F}٢٣/٠٦/٢٠١٨, ١٣:٤٥ - Luke: مرحبا".to_string(),
+                ..Default::default()
+            },
+            RawMessage {
+                system: false,
+                msg: "\u{200F}٢٤/٠٦/٢٠١٨, ٩:٠٥ - Leia: ١٢٣".to_string(),
+                ..Default::default()
+            },
+        ];
+        let parsed = parse_messages(&messages, &ParseStringOptions::default()).unwrap();
+
+        assert_eq!(parsed[0].date, Utc.with_ymd_and_hms(2018, 6, 23, 13, 45, 0).unwrap());
+        assert_eq!(parsed[1].date, Utc.with_ymd_and_hms(2018, 6, 24, 9, 5, 0).unwrap());
+        assert_eq!(parsed[0].message, "مرحبا");
+        // Only the date and time are normalized
+        assert_eq!(parsed[1].message, "١٢٣");
+    }
+
+    /// Parses made-up headers like `[1529762100] ` (a Unix timestamp).
+    struct EpochParser;
+
+    impl DateTimeParser for EpochParser {
+        fn parse(&self, header: &str) -> Option<(NaiveDateTime, usize)> {
+            let end = header.strip_prefix('[')?.find(']')? + 1;
+            let seconds: i64 = header[1..end].parse().ok()?;
+            let date = DateTime::from_timestamp(seconds, 0)?.naive_utc();
+            Some((date, end + 2))
+        }
+    }
+
+    #[test]
+    fn test_parse_lines_with_custom_parser() {
+        let lines = ["[1529762100] Luke: Hey!", "second line", "[1529762160] Luke added Leia", "[1529762220] Leia: \u{200E}IMG-1.jpg (file attached)"];
+        let options = ParseStringOptions {
+            parse_attachments: true,
+            ..Default::default()
+        };
+        let messages = parse_lines_with(lines, &EpochParser, &options).unwrap();
+
+        assert_eq!(messages.len(), 3);
+        assert_eq!(messages[0].date, Utc.with_ymd_and_hms(2018, 6, 23, 13, 55, 0).unwrap());
+        assert_eq!(messages[0].author.as_deref(), Some("Luke"));
+        assert_eq!(messages[0].message, "Hey!\nsecond line");
+        assert_eq!(messages[1].author, None);
+        assert_eq!(messages[1].system_kind, Some(crate::models::SystemMessageKind::ParticipantAdded("Leia".to_string())));
+        assert_eq!(messages[2].attachment.as_ref().unwrap().file_name, "IMG-1.jpg");
+    }
+
+    #[test]
+    fn test_parse_messages_custom_parser_error_policy() {
+        let messages = vec![
+            RawMessage {
+                system: false,
+                msg: "[1529762100] Luke: Hey!".to_string(),
+                ..Default::default()
+            },
+            RawMessage {
+                system: false,
+                msg: "23/06/2018, 01:55 - Luke: Hey!".to_string(),
+                ..Default::default()
+            },
+        ];
+        let options = |on_parse_error| ParseStringOptions {
+            date_time_parser: Some(Box::new(EpochParser)),
+            on_parse_error,
+            ..Default::default()
+        };
+
+        assert_eq!(parse_messages(&messages, &options(ErrorPolicy::Skip)).unwrap().len(), 1);
+        assert!(matches!(parse_messages(&messages, &options(ErrorPolicy::Fail)), Err(ParseError::MalformedMessage(_))));
+        assert_eq!(
+            parse_messages_with_warnings(&messages, &options(ErrorPolicy::Fail)).1,
+            [ParseWarning::SkippedMessage("23/06/2018, 01:55 - Luke: Hey!".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_month_name_date_time_parser() {
+        let parser = MonthNameDateTimeParser::new(&crate::datetime::month_map_for_locale("de").unwrap(), 70);
+        let date = |y, m, d, h, min| NaiveDate::from_ymd_opt(y, m, d).unwrap().and_hms_opt(h, min, 0).unwrap();
+
+        let header = "7. Januar 2023 14:30 - a: m";
+        assert_eq!(parser.parse(header), Some((date(2023, 1, 7, 14, 30), header.len() - 4)));
+        assert_eq!(parser.parse("[31. DEZEMBER 22, 9:05:00] a: m").map(|(d, _)| d), Some(date(2022, 12, 31, 9, 5)));
+        assert_eq!(parser.parse("7. Januar 2023 um 2:30 PM - a: m").map(|(d, _)| d), Some(date(2023, 1, 7, 14, 30)));
+        assert_eq!(parser.parse("31. Februar 2023 14:30 - a: m"), None);
+        assert_eq!(parser.parse("7. Januar 2023 25:30 - a: m"), None);
+
+        let months: HashMap<String, u32> = [("Enero".to_string(), 1)].into_iter().collect();
+        let parser = MonthNameDateTimeParser::new(&months, 70);
+        assert_eq!(parser.parse("7 de enero de 2023, 14:30 - a: m").map(|(d, _)| d), Some(date(2023, 1, 7, 14, 30)));
+        assert_eq!(parser.parse("7 de febrero de 2023, 14:30 - a: m"), None);
+        assert_eq!(parser.parse("07/01/2023, 14:30 - a: m"), None);
+    }
+
+    #[test]
+    fn test_parse_lines_with_multiline_system_message() {
+        let parser = MonthNameDateTimeParser::new(&crate::datetime::month_map_for_locale("de").unwrap(), 70);
+        let lines = [
+            "7. Januar 2023 14:30 - Luke hat die Gruppe erstellt",
+            "Hinweis: neu",
+            "7. Januar 2023 14:31 - Leia: Hallo",
+            "Frage: wer?",
+        ];
+        let messages = parse_lines_with(lines, &parser, &ParseStringOptions::default()).unwrap();
+
+        assert_eq!(messages[0].author, None);
+        assert_eq!(messages[0].message, "Luke hat die Gruppe erstellt\nHinweis: neu");
+        assert_eq!(messages[1].author.as_deref(), Some("Leia"));
+        assert_eq!(messages[1].message, "Hallo\nFrage: wer?");
+    }
+
     #[test]
     fn test_parse_messages_sticker_with_u200e() {
         // This simulates a sticker message with U+200E both at the beginning and before "sticker omitted"
@@ -587,8 +2017,9 @@ mod tests {
         let messages = vec![RawMessage {
             system: false,
             msg: sticker_message,
+            ..Default::default()
         }];
-        let parsed = parse_messages(&messages, &ParseStringOptions::default());
+        let parsed = parse_messages(&messages, &ParseStringOptions::default()).unwrap();
 
         assert_eq!(parsed[0].date.year(), 2021);
         assert_eq!(parsed[0].date.month(), 10);
@@ -600,4 +2031,65 @@ mod tests {
         // The message should NOT contain the U+200E character
         assert_eq!(parsed[0].message, "sticker omitted");
     }
+
+    #[test]
+    fn test_parse_messages_zwj_author() {
+        let family = "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}";
+        let rainbow_flag = "\u{1F3F3}\u{FE0F}\u{200D}\u{1F308}";
+        let messages = vec![
+            RawMessage {
+                system: false,
+                msg: format!("23/06/2018, 01:55 - \u{200E}{}\u{200F}: Hey!", family),
+                ..Default::default()
+            },
+            RawMessage {
+                system: false,
+                msg: format!("\u{200E}[23/06/2018, 01:55:00] \u{200F}{} Bob\u{200E}: Hey!", rainbow_flag),
+                ..Default::default()
+            },
+        ];
+        let parsed = parse_messages(&messages, &ParseStringOptions::default()).unwrap();
+
+        assert_eq!(parsed[0].author, Some(family.to_string()));
+        assert_eq!(parsed[1].author, Some(format!("{} Bob", rainbow_flag)));
+        assert!(parsed[0].author.as_ref().unwrap().contains('\u{200D}'));
+        assert_eq!(parsed[0].message, "Hey!");
+    }
+
+    #[test]
+    fn test_parse_messages_author_with_colons() {
+        let lines = [
+            "23/06/2018, 01:55 - Company: Support: Hello",
+            "23/06/2018, 01:56 - A: B: message",
+            "23/06/2018, 01:57 - http://example.com: Click here",
+            "23/06/2018, 01:58 - \u{200E}Company: Support\u{200F}: Hi: again",
+        ];
+        let split = |options: &ParseStringOptions| {
+            parse_messages(&make_array_of_messages_slice(&lines), options)
+                .unwrap()
+                .into_iter()
+                .map(|m| (m.author.unwrap(), m.message))
+                .collect::<Vec<_>>()
+        };
+        let pairs = |pairs: [(&str, &str); 4]| pairs.map(|(a, m)| (a.to_string(), m.to_string())).to_vec();
+
+        // Bodies contain `: ` far more often than names do, so the first one
+        // ends the author by default
+        assert_eq!(
+            split(&ParseStringOptions::default()),
+            pairs([("Company", "Support: Hello"), ("A", "B: message"), ("http://example.com", "Click here"), ("Company", "Support: Hi: again")])
+        );
+
+        let options = ParseStringOptions {
+            known_authors: vec!["Company".to_string(), "Company: Support".to_string(), "A: B".to_string()],
+            ..Default::default()
+        };
+        let expected = pairs([("Company: Support", "Hello"), ("A: B", "message"), ("http://example.com", "Click here"), ("Company: Support", "Hi: again")]);
+        assert_eq!(split(&options), expected);
+
+        let parser = MonthNameDateTimeParser::new(&[("june".to_string(), 6)].into_iter().collect(), 70);
+        let lines: Vec<String> = lines.iter().map(|line| line.replace("23/06/2018", "23 June 2018")).collect();
+        let parsed = parse_lines_with(lines.iter().map(String::as_str), &parser, &options).unwrap();
+        assert_eq!(parsed.into_iter().map(|m| (m.author.unwrap(), m.message)).collect::<Vec<_>>(), expected);
+    }
 }
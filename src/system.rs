@@ -0,0 +1,477 @@
+use crate::models::{MediaKind, Message, SystemMessageKind};
+use crate::parser::strip_directional_marks;
+use lazy_static::lazy_static;
+use regex::Regex;
+use chrono::{DateTime, Utc};
+use std::collections::{HashMap, HashSet};
+
+lazy_static! {
+    static ref REGEX_PHONE_NUMBER: Regex = Regex::new(r"\+?\d[\d\s().-]{4,}\d").unwrap();
+    static ref REGEX_END_TO_END: Regex = Regex::new(r"(?i)end-to-end encrypt").unwrap();
+    static ref REGEX_GROUP_NAME_CHANGED: Regex = Regex::new(r#"^.+? changed (?:the group name|the subject)(?: from ["“].*["”])? to ["“](.*)["”]$"#).unwrap();
+    static ref REGEX_PARTICIPANT_ADDED: Regex = Regex::new(r"^.+? added (.+)$").unwrap();
+    static ref REGEX_PARTICIPANT_REMOVED: Regex = Regex::new(r"^.+? removed (.+)$").unwrap();
+    static ref REGEX_PARTICIPANT_LEFT: Regex = Regex::new(r"^.+ left$").unwrap();
+    static ref REGEX_ADMIN: Regex = Regex::new(r"^(?:(.+?) (?:is|are) now an admin|(You)'re now an admin|.+? made (.+?) an admin)$").unwrap();
+    static ref REGEX_MESSAGE_TIMER: Regex = Regex::new(r"(?i)disappearing messages|message timer").unwrap();
+    static ref DEFAULT_PHRASES: SystemPhrases = SystemPhrases::default();
+}
+
+/// An event described by a system message, as found by
+/// [`detect_system_event`]: a `GroupCreated` or `NumberChanged` notice.
+pub type SystemEvent = SystemMessageKind;
+
+/// The phrases used to recognize system events.
+///
+/// Defaults to the English phrases; replace or extend them to recognize exports
+/// made in other languages. Phrases are matched case-insensitively anywhere in
+/// the system message.
+#[derive(Debug, Clone)]
+pub struct SystemPhrases {
+    /// Phrases of the notices shown when a participant changes their number.
+    ///
+    /// A phone number right after the phrase is taken as the new number.
+    pub number_changed: Vec<String>,
+    /// Phrases between the old and the new number in the notices shown when a
+    /// participant changes their number, e.g. `" changed to "`.
+    ///
+    /// Only recognized when a phone number follows the phrase, since they are
+    /// common in other notices.
+    pub number_changed_to: Vec<String>,
+    /// Phrases of the notices shown when a participant creates a group.
+    ///
+    /// The text after the phrase is taken as the name of the group.
+    pub group_created: Vec<String>,
+    /// Phrases following the count in the summaries of media left out of an
+    /// export (e.g. `127 images omitted`), with the kind of media they count.
+    pub media_omitted: Vec<(String, MediaKind)>,
+}
+
+impl Default for SystemPhrases {
+    fn default() -> Self {
+        SystemPhrases {
+            number_changed: vec![
+                "changed to a new number".to_string(),
+                "changed their phone number".to_string(),
+            ],
+            number_changed_to: vec![" changed to ".to_string()],
+            group_created: vec!["created group".to_string()],
+            media_omitted: [
+                ("images omitted", MediaKind::Image),
+                ("image omitted", MediaKind::Image),
+                ("photos omitted", MediaKind::Image),
+                ("photo omitted", MediaKind::Image),
+                ("videos omitted", MediaKind::Video),
+                ("video omitted", MediaKind::Video),
+                ("audios omitted", MediaKind::Audio),
+                ("audio omitted", MediaKind::Audio),
+                ("stickers omitted", MediaKind::Sticker),
+                ("sticker omitted", MediaKind::Sticker),
+                ("GIFs omitted", MediaKind::Gif),
+                ("GIF omitted", MediaKind::Gif),
+                ("documents omitted", MediaKind::Document),
+                ("document omitted", MediaKind::Document),
+            ]
+            .into_iter()
+            .map(|(phrase, kind)| (phrase.to_string(), kind))
+            .collect(),
+        }
+    }
+}
+
+/// Finds the first phrase contained in `text`, ignoring case, and returns the
+/// part of `text` following it.
+fn find_phrase<'a>(text: &'a str, phrases: &[String]) -> Option<&'a str> {
+    phrases.iter().find_map(|phrase| {
+        text.char_indices().find_map(|(idx, _)| {
+            let mut rest = text[idx..].chars();
+            let matches = phrase.chars().all(|expected| {
+                rest.next()
+                    .is_some_and(|c| c.to_lowercase().eq(expected.to_lowercase()))
+            });
+            if matches { Some(rest.as_str()) } else { None }
+        })
+    })
+}
+
+/// Removes the quotes around a group name, if any.
+fn unquote(name: &str) -> &str {
+    [('"', '"'), ('\u{201C}', '\u{201D}'), ('«', '»')]
+        .iter()
+        .find_map(|(open, close)| name.strip_prefix(*open)?.strip_suffix(*close))
+        .unwrap_or(name)
+}
+
+/// Detects the event described by a system message, i.e. the group creations
+/// and number changes recognized with `phrases`.
+///
+/// Output is `None` for user messages and for system messages that don't match
+/// any of the phrases.
+pub fn detect_system_event(message: &Message, phrases: &SystemPhrases) -> Option<SystemEvent> {
+    if message.is_user() {
+        return None;
+    }
+
+    // Exports can put directional marks anywhere, e.g. right before "omitted"
+    detect_event_in_text(&strip_directional_marks(&message.message), phrases)
+}
+
+/// Detects the event described by the text of a system message, without its
+/// directional marks.
+fn detect_event_in_text(text: &str, phrases: &SystemPhrases) -> Option<SystemEvent> {
+    if let Some(rest) = find_phrase(text, &phrases.group_created) {
+        let name = unquote(rest.trim()).to_string();
+        return Some(SystemMessageKind::GroupCreated { name });
+    }
+
+    find_phrase(text, &phrases.number_changed)
+        .map(|rest| SystemMessageKind::NumberChanged { new_number: leading_phone_number(rest) })
+        .or_else(|| {
+            let new_number = leading_phone_number(find_phrase(text, &phrases.number_changed_to)?)?;
+            Some(SystemMessageKind::NumberChanged { new_number: Some(new_number) })
+        })
+}
+
+/// Returns the phone number at the start of `text`, if any.
+fn leading_phone_number(text: &str) -> Option<String> {
+    REGEX_PHONE_NUMBER
+        .find(text)
+        .filter(|m| text[..m.start()].trim().is_empty())
+        .map(|m| m.as_str().to_string())
+}
+
+/// Classifies the text of a system message, recognizing the English notices.
+///
+/// Same as [`classify_system_message_with_phrases`] with the default phrases.
+pub fn classify_system_message(text: &str) -> SystemMessageKind {
+    classify_system_message_with_phrases(text, &DEFAULT_PHRASES)
+}
+
+/// Classifies the text of a system message. Group creations and number
+/// changes are recognized with `phrases`, like [`detect_system_event`] does,
+/// the other notices in English only.
+pub fn classify_system_message_with_phrases(text: &str, phrases: &SystemPhrases) -> SystemMessageKind {
+    let text = strip_directional_marks(text);
+    let text = text.trim();
+    let first_capture = |caps: regex::Captures| {
+        caps.iter().skip(1).flatten().next().map_or(String::new(), |m| m.as_str().to_string())
+    };
+
+    if REGEX_END_TO_END.is_match(text) {
+        return SystemMessageKind::EndToEndEncryption;
+    }
+    if let Some(kind) = detect_event_in_text(text, phrases) {
+        return kind;
+    }
+
+    if let Some(caps) = REGEX_GROUP_NAME_CHANGED.captures(text) {
+        SystemMessageKind::GroupNameChanged { new_name: caps[1].to_string() }
+    } else if let Some(caps) = REGEX_ADMIN.captures(text) {
+        SystemMessageKind::AdminChanged(first_capture(caps))
+    } else if REGEX_MESSAGE_TIMER.is_match(text) {
+        SystemMessageKind::MessageTimerChanged
+    } else if let Some(caps) = REGEX_PARTICIPANT_ADDED.captures(text) {
+        SystemMessageKind::ParticipantAdded(caps[1].to_string())
+    } else if let Some(caps) = REGEX_PARTICIPANT_REMOVED.captures(text) {
+        SystemMessageKind::ParticipantRemoved(caps[1].to_string())
+    } else if REGEX_PARTICIPANT_LEFT.is_match(text) {
+        SystemMessageKind::ParticipantLeft
+    } else {
+        SystemMessageKind::Unknown
+    }
+}
+
+/// Information about a chat as a whole, gathered from its system messages.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct ChatMetadata {
+    /// The current name of the group: the name it was created with, or the
+    /// last one it was renamed to. `None` if no message mentions it.
+    pub group_name: Option<String>,
+    /// Whether the chat is a group chat, i.e. contains system messages about
+    /// the group, its participants or its admins.
+    pub is_group_chat: bool,
+    /// The number of distinct participants who sent a message. `None` if
+    /// there are no user messages.
+    pub participant_count: Option<usize>,
+    /// The date of the first message of the export.
+    pub export_start: Option<DateTime<Utc>>,
+    /// The number of media files left out of the export, by kind, as reported
+    /// by the `X images omitted` summaries.
+    pub omitted_media: HashMap<MediaKind, usize>,
+}
+
+impl ChatMetadata {
+    /// The total number of media files left out of the export.
+    pub fn total_omitted_media(&self) -> usize {
+        self.omitted_media.values().sum()
+    }
+}
+
+/// Gathers the metadata of a chat from its system messages, using the English
+/// phrases.
+pub fn parse_metadata(messages: &[Message]) -> ChatMetadata {
+    parse_metadata_with_phrases(messages, &SystemPhrases::default())
+}
+
+/// Gathers the metadata of a chat from its system messages, using the given
+/// phrases.
+///
+/// The notices are recognized with [`classify_system_message_with_phrases`],
+/// unless the messages already have a known `system_kind`.
+pub fn parse_metadata_with_phrases(messages: &[Message], phrases: &SystemPhrases) -> ChatMetadata {
+    let media_omitted: Vec<(Regex, MediaKind)> = phrases
+        .media_omitted
+        .iter()
+        .map(|(phrase, kind)| {
+            let regex = Regex::new(&format!(r"(?i)(\d+)\s+{}", regex::escape(phrase))).unwrap();
+            (regex, *kind)
+        })
+        .collect();
+    let mut metadata = ChatMetadata {
+        export_start: messages.first().map(|m| m.date),
+        ..Default::default()
+    };
+    let participants: HashSet<&str> = messages.iter().filter_map(|m| m.author.as_deref()).collect();
+    if !participants.is_empty() {
+        metadata.participant_count = Some(participants.len());
+    }
+
+    for message in messages.iter().filter(|m| m.is_system()) {
+        let kind = match &message.system_kind {
+            Some(kind) if *kind != SystemMessageKind::Unknown => kind.clone(),
+            _ => classify_system_message_with_phrases(&message.message, phrases),
+        };
+        metadata.is_group_chat |= matches!(
+            kind,
+            SystemMessageKind::GroupCreated { .. }
+                | SystemMessageKind::GroupNameChanged { .. }
+                | SystemMessageKind::ParticipantAdded(_)
+                | SystemMessageKind::ParticipantRemoved(_)
+                | SystemMessageKind::ParticipantLeft
+                | SystemMessageKind::AdminChanged(_)
+        );
+        match kind {
+            SystemMessageKind::GroupCreated { name } => {
+                metadata.group_name.get_or_insert(name);
+            }
+            SystemMessageKind::GroupNameChanged { new_name } => metadata.group_name = Some(new_name),
+            SystemMessageKind::EndToEndEncryption => {
+                metadata.is_group_chat |= message.message.to_lowercase().contains("group");
+            }
+            _ => {}
+        }
+
+        for (regex, kind) in &media_omitted {
+            for caps in regex.captures_iter(&strip_directional_marks(&message.message)) {
+                let count: usize = caps[1].parse().unwrap_or(0);
+                *metadata.omitted_media.entry(*kind).or_default() += count;
+            }
+        }
+    }
+
+    metadata
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn system_message(text: &str) -> Message {
+        Message {
+            date: DateTime::<Utc>::default(),
+            author: None,
+            message: text.to_string(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_detect_number_changed() {
+        let phrases = SystemPhrases::default();
+
+        assert_eq!(
+            detect_system_event(&system_message("+41 79 123 45 67 changed to a new number. Tap to message or add the new number."), &phrases),
+            Some(SystemMessageKind::NumberChanged { new_number: None })
+        );
+        assert_eq!(
+            detect_system_event(&system_message("+41 79 123 45 67 changed to +41 79 765 43 21"), &phrases),
+            Some(SystemMessageKind::NumberChanged { new_number: Some("+41 79 765 43 21".to_string()) })
+        );
+        assert_eq!(
+            detect_system_event(&system_message("Luke changed their phone number to a new number. Tap to message or add the new number."), &phrases),
+            Some(SystemMessageKind::NumberChanged { new_number: None })
+        );
+        assert_eq!(detect_system_event(&system_message("Messages to this group are now secured with end-to-end encryption."), &phrases), None);
+        assert_eq!(detect_system_event(&system_message("Luke changed to the new group settings"), &phrases), None);
+        assert_eq!(detect_system_event(&system_message("The subject changed to \"Jedi\""), &phrases), None);
+    }
+
+    #[test]
+    fn test_detect_group_created() {
+        let phrases = SystemPhrases::default();
+
+        assert_eq!(
+            detect_system_event(&system_message("You created group \"ShortChat\""), &phrases),
+            Some(SystemMessageKind::GroupCreated { name: "ShortChat".to_string() })
+        );
+        assert_eq!(
+            detect_system_event(&system_message("Luke Created Group “Rebel Alliance”"), &phrases),
+            Some(SystemMessageKind::GroupCreated { name: "Rebel Alliance".to_string() })
+        );
+    }
+
+    #[test]
+    fn test_detect_number_changed_localized() {
+        let phrases = SystemPhrases {
+            number_changed: vec!["hat zu einer neuen Nummer gewechselt".to_string()],
+            ..Default::default()
+        };
+        let message = system_message("+49 151 1234567 hat zu einer neuen Nummer gewechselt. Tippe, um eine Nachricht zu senden.");
+
+        assert_eq!(detect_system_event(&message, &phrases), Some(SystemMessageKind::NumberChanged { new_number: None }));
+        assert_eq!(detect_system_event(&message, &SystemPhrases::default()), None);
+    }
+
+    #[test]
+    fn test_detect_system_event_user_message() {
+        let mut message = system_message("I changed to a new number");
+        message.author = Some("Luke".to_string());
+
+        assert_eq!(detect_system_event(&message, &SystemPhrases::default()), None);
+    }
+
+    #[test]
+    fn test_classify_system_message() {
+        let cases = [
+            ("Messages to this group are now secured with end-to-end encryption. Tap for more info.", SystemMessageKind::EndToEndEncryption),
+            ("\u{200E}Messages and calls are end-to-end encrypted. No one outside of this chat, not even WhatsApp, can read or listen to them.", SystemMessageKind::EndToEndEncryption),
+            ("You created group \"ShortChat\"", SystemMessageKind::GroupCreated { name: "ShortChat".to_string() }),
+            ("Luke created group “Rebel Alliance”", SystemMessageKind::GroupCreated { name: "Rebel Alliance".to_string() }),
+            ("Luke changed the group name to \"Jedi\"", SystemMessageKind::GroupNameChanged { new_name: "Jedi".to_string() }),
+            ("Luke changed the subject from \"Rebels\" to \"Jedi\"", SystemMessageKind::GroupNameChanged { new_name: "Jedi".to_string() }),
+            ("Luke added Leia", SystemMessageKind::ParticipantAdded("Leia".to_string())),
+            ("You added Han and Chewbacca", SystemMessageKind::ParticipantAdded("Han and Chewbacca".to_string())),
+            ("Luke removed Han", SystemMessageKind::ParticipantRemoved("Han".to_string())),
+            ("Han left", SystemMessageKind::ParticipantLeft),
+            ("You're now an admin", SystemMessageKind::AdminChanged("You".to_string())),
+            ("Leia is now an admin", SystemMessageKind::AdminChanged("Leia".to_string())),
+            ("+41 79 123 45 67 changed to a new number. Tap to message or add the new number.", SystemMessageKind::NumberChanged { new_number: None }),
+            ("Luke changed their phone number to a new number. Tap to message or add the new number.", SystemMessageKind::NumberChanged { new_number: None }),
+            ("+41 79 123 45 67 changed to +41 79 765 43 21", SystemMessageKind::NumberChanged { new_number: Some("+41 79 765 43 21".to_string()) }),
+            ("Luke turned on disappearing messages. New messages will disappear from this chat 7 days after they're sent.", SystemMessageKind::MessageTimerChanged),
+            ("You turned off disappearing messages.", SystemMessageKind::MessageTimerChanged),
+            ("Luke changed the group description", SystemMessageKind::Unknown),
+        ];
+
+        for (text, kind) in cases {
+            assert_eq!(classify_system_message(text), kind, "{}", text);
+        }
+    }
+
+    #[test]
+    fn test_classify_system_message_with_phrases() {
+        let phrases = SystemPhrases {
+            number_changed: vec!["hat zu einer neuen Nummer gewechselt".to_string()],
+            group_created: vec!["hat die Gruppe erstellt:".to_string()],
+            ..Default::default()
+        };
+        let cases = [
+            ("+49 151 1234567 hat zu einer neuen Nummer gewechselt.", SystemMessageKind::NumberChanged { new_number: None }),
+            ("Luke hat die Gruppe erstellt: „Rebellen“", SystemMessageKind::GroupCreated { name: "„Rebellen“".to_string() }),
+            ("+41 79 123 45 67 changed to +41 79 765 43 21", SystemMessageKind::NumberChanged { new_number: Some("+41 79 765 43 21".to_string()) }),
+            ("Luke changed to the new group settings", SystemMessageKind::Unknown),
+        ];
+
+        for (text, kind) in cases {
+            assert_eq!(classify_system_message_with_phrases(text, &phrases), kind, "{}", text);
+            let message = system_message(text);
+            let event = detect_system_event(&message, &phrases);
+            assert_eq!(event.is_some(), kind != SystemMessageKind::Unknown, "{}", text);
+        }
+        assert_eq!(classify_system_message("+49 151 1234567 hat zu einer neuen Nummer gewechselt."), SystemMessageKind::Unknown);
+    }
+
+    #[test]
+    fn test_parse_metadata_media_omitted() {
+        let messages = vec![
+            system_message("127 images omitted, 3 videos omitted"),
+            system_message("1 image omitted"),
+            system_message("You created group \"Test\""),
+        ];
+        let metadata = parse_metadata(&messages);
+
+        assert_eq!(metadata.omitted_media.get(&MediaKind::Image), Some(&128));
+        assert_eq!(metadata.omitted_media.get(&MediaKind::Video), Some(&3));
+        assert_eq!(metadata.omitted_media.get(&MediaKind::Audio), None);
+        assert_eq!(metadata.total_omitted_media(), 131);
+    }
+
+    #[test]
+    fn test_parse_metadata_directional_marks() {
+        let messages = vec![
+            system_message("\u{200E}3 images\u{200E} omitted"),
+            system_message("+41 79 123 45 67 \u{200E}changed to\u{200E} +41 79 765 43 21"),
+        ];
+
+        assert_eq!(parse_metadata(&messages).omitted_media.get(&MediaKind::Image), Some(&3));
+        assert_eq!(
+            detect_system_event(&messages[1], &SystemPhrases::default()),
+            Some(SystemMessageKind::NumberChanged { new_number: Some("+41 79 765 43 21".to_string()) })
+        );
+    }
+
+    #[test]
+    fn test_parse_metadata_group() {
+        let mut luke = system_message("Hey!");
+        luke.author = Some("Luke".to_string());
+        luke.date = DateTime::from_timestamp(1000, 0).unwrap();
+        let mut leia = system_message("Hi!");
+        leia.author = Some("Leia".to_string());
+        let messages = vec![
+            system_message("You created group \"Rebels\""),
+            luke,
+            leia,
+            system_message("Luke changed the group name to \"Jedi\""),
+        ];
+        let metadata = parse_metadata(&messages);
+
+        assert_eq!(metadata.group_name.as_deref(), Some("Jedi"));
+        assert!(metadata.is_group_chat);
+        assert_eq!(metadata.participant_count, Some(2));
+        assert_eq!(metadata.export_start, Some(DateTime::<Utc>::default()));
+
+        let metadata = parse_metadata(&messages[1..3]);
+        assert_eq!(metadata.group_name, None);
+        assert!(!metadata.is_group_chat);
+        assert_eq!(metadata.export_start, Some(DateTime::from_timestamp(1000, 0).unwrap()));
+        assert_eq!(parse_metadata(&[]), ChatMetadata::default());
+    }
+
+    #[test]
+    fn test_parse_metadata_group_created_localized() {
+        let phrases = SystemPhrases {
+            group_created: vec!["hat die Gruppe erstellt:".to_string()],
+            ..Default::default()
+        };
+        let mut message = system_message("Luke hat die Gruppe erstellt: Rebellen");
+        // As classified while parsing, with the English phrases
+        message.system_kind = Some(SystemMessageKind::Unknown);
+        let metadata = parse_metadata_with_phrases(std::slice::from_ref(&message), &phrases);
+
+        assert_eq!(metadata.group_name.as_deref(), Some("Rebellen"));
+        assert!(metadata.is_group_chat);
+        assert_eq!(parse_metadata(&[message]).group_name, None);
+    }
+
+    #[test]
+    fn test_parse_metadata_media_omitted_localized() {
+        let phrases = SystemPhrases {
+            media_omitted: vec![("Bilder weggelassen".to_string(), MediaKind::Image)],
+            ..Default::default()
+        };
+        let messages = vec![system_message("12 Bilder weggelassen")];
+
+        assert_eq!(parse_metadata_with_phrases(&messages, &phrases).omitted_media.get(&MediaKind::Image), Some(&12));
+        assert_eq!(parse_metadata(&messages).total_omitted_media(), 0);
+    }
+}
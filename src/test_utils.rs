@@ -0,0 +1,162 @@
+//! Builders for synthetic chat exports, to test code that consumes the parsed
+//! messages without shipping real exports. Enabled by the `test-utils` feature.
+
+use chrono::{DateTime, Utc};
+
+/// The layout of the message headers written by [`ExportBuilder`], matching
+/// the exports of different platforms and locales.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DateFormat {
+    /// `23/06/2018, 13:55 - `, as in Android exports with European locales.
+    #[default]
+    DayMonthYear,
+    /// `6/23/18, 1:55 PM - `, as in Android exports with the US locale.
+    MonthDayYear12Hour,
+    /// `23.06.18, 13:55 - `, as in Android exports with German locales.
+    Dotted,
+    /// `[23/06/2018, 13:55:12] `, as in iOS exports. The only format that keeps
+    /// the seconds.
+    Bracketed,
+}
+
+impl DateFormat {
+    /// Formats the header of a message sent at `date`.
+    fn header(self, date: &DateTime<Utc>) -> String {
+        match self {
+            DateFormat::DayMonthYear => date.format("%d/%m/%Y, %H:%M - ").to_string(),
+            DateFormat::MonthDayYear12Hour => date.format("%-m/%-d/%y, %-I:%M %p - ").to_string(),
+            DateFormat::Dotted => date.format("%d.%m.%y, %H:%M - ").to_string(),
+            DateFormat::Bracketed => date.format("[%d/%m/%Y, %H:%M:%S] ").to_string(),
+        }
+    }
+
+    /// Formats the body of a message with only an attachment.
+    fn attachment(self, file_name: &str) -> String {
+        match self {
+            DateFormat::Bracketed => format!("<attached: {}>", file_name),
+            _ => format!("{} (file attached)", file_name),
+        }
+    }
+}
+
+/// A message added to an [`ExportBuilder`].
+#[derive(Debug, Clone)]
+enum Entry {
+    User { author: String, body: String, date: DateTime<Utc> },
+    System { body: String, date: DateTime<Utc> },
+    Attachment { author: String, file_name: String, date: DateTime<Utc> },
+}
+
+/// Builds the text of a chat export from a list of messages.
+///
+/// Bodies with several lines become multiline messages. Seconds are dropped
+/// unless the format is [`DateFormat::Bracketed`].
+#[derive(Debug, Clone, Default)]
+pub struct ExportBuilder {
+    format: DateFormat,
+    entries: Vec<Entry>,
+}
+
+impl ExportBuilder {
+    /// Creates an empty export using [`DateFormat::DayMonthYear`].
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the layout of the message headers.
+    pub fn with_date_format(&mut self, format: DateFormat) -> &mut Self {
+        self.format = format;
+        self
+    }
+
+    /// Adds a message sent by `author`.
+    pub fn add_message(&mut self, author: &str, body: &str, dt: DateTime<Utc>) -> &mut Self {
+        self.entries.push(Entry::User {
+            author: author.to_string(),
+            body: body.to_string(),
+            date: dt,
+        });
+        self
+    }
+
+    /// Adds a system message, such as a participant joining the group.
+    pub fn add_system_message(&mut self, body: &str, dt: DateTime<Utc>) -> &mut Self {
+        self.entries.push(Entry::System {
+            body: body.to_string(),
+            date: dt,
+        });
+        self
+    }
+
+    /// Adds a message sent by `author` with only an attachment, using the
+    /// attachment notice of the date format.
+    pub fn add_attachment(&mut self, author: &str, filename: &str, dt: DateTime<Utc>) -> &mut Self {
+        self.entries.push(Entry::Attachment {
+            author: author.to_string(),
+            file_name: filename.to_string(),
+            date: dt,
+        });
+        self
+    }
+
+    /// Writes the export, one message after the other in the order they were
+    /// added.
+    pub fn build(&self) -> String {
+        self.entries
+            .iter()
+            .map(|entry| match entry {
+                Entry::User { author, body, date } => format!("{}{}: {}", self.format.header(date), author, body),
+                Entry::System { body, date } => format!("{}{}", self.format.header(date), body),
+                Entry::Attachment { author, file_name, date } => {
+                    format!("{}{}: {}", self.format.header(date), author, self.format.attachment(file_name))
+                }
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::ParseStringOptions;
+    use crate::parse_string;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_export_builder_round_trip() {
+        let first = Utc.with_ymd_and_hms(2018, 6, 23, 13, 55, 0).unwrap();
+        let second = Utc.with_ymd_and_hms(2018, 6, 24, 1, 5, 0).unwrap();
+
+        for format in [DateFormat::DayMonthYear, DateFormat::MonthDayYear12Hour, DateFormat::Dotted, DateFormat::Bracketed] {
+            let export = ExportBuilder::new()
+                .with_date_format(format)
+                .add_system_message("Luke added Leia", first)
+                .add_message("Luke", "Hey!\nHow are you?", first)
+                .add_attachment("Leia", "IMG-20180624-WA0001.jpg", second)
+                .build();
+            let options = ParseStringOptions {
+                parse_attachments: true,
+                ..Default::default()
+            };
+            let messages = parse_string(&export, Some(options)).unwrap();
+
+            assert_eq!(messages.len(), 3, "{:?}", format);
+            assert_eq!((messages[0].date, messages[0].author.as_deref(), messages[0].message.as_str()), (first, None, "Luke added Leia"));
+            assert_eq!((messages[1].date, messages[1].author.as_deref(), messages[1].message.as_str()), (first, Some("Luke"), "Hey!\nHow are you?"));
+            assert_eq!((messages[2].date, messages[2].author.as_deref()), (second, Some("Leia")));
+            assert_eq!(messages[2].attachment.as_ref().unwrap().file_name, "IMG-20180624-WA0001.jpg", "{:?}", format);
+        }
+    }
+
+    #[test]
+    fn test_export_builder_seconds() {
+        let date = Utc.with_ymd_and_hms(2018, 6, 23, 13, 55, 12).unwrap();
+        let mut builder = ExportBuilder::new();
+        builder.add_message("Luke", "Hey!", date);
+
+        assert_eq!(builder.build(), "23/06/2018, 13:55 - Luke: Hey!");
+        assert_eq!(builder.with_date_format(DateFormat::Bracketed).build(), "[23/06/2018, 13:55:12] Luke: Hey!");
+        assert_eq!(parse_string(&builder.build(), None).unwrap()[0].date, date);
+    }
+}
@@ -1,6 +1,15 @@
-use chrono::Utc;
+use chrono::{Timelike, Utc};
 use chrono::offset::TimeZone;
-use wc_parser::parse_string;
+use wc_parser::analysis::{compute_author_stats, MessageStats};
+use wc_parser::datetime::month_map_for_locale;
+use wc_parser::error::{ParseError, ParseLineErrorReason, ParseWarning, SizeLimit};
+use wc_parser::export::{format_messages, messages_to_markdown, MessageFormat};
+use wc_parser::models::{ParseStringOptions, SystemMessageKind};
+use wc_parser::{
+    is_message_start, is_system_message_start, load_and_merge, parse_bytes, parse_chat, parse_metadata, parse_epoch, parse_file, parse_file_authors, parse_file_count, parse_file_date_range, parse_since, parse_string,
+    parse_string_authors, parse_string_count, parse_string_date_range, parse_string_lenient,
+    parse_files, parse_string_verbose, parse_string_with_stats,
+};
 
 const CHAT_EXAMPLE: &str = r#"06/03/2017, 00:45 - Messages to this group are now secured with end-to-end encryption. Tap for more info.
 06/03/2017, 00:45 - You created group "ShortChat"
@@ -26,6 +35,118 @@ fn test_parse_string_multiline() {
     assert_eq!(messages[4].message, "How are you?\nIs everything alright?");
 }
 
+#[test]
+fn test_message_stats() {
+    let messages = parse_string(CHAT_EXAMPLE, None).unwrap();
+    let stats = MessageStats::compute(&messages);
+
+    assert_eq!(stats.total_messages, 5);
+    assert_eq!(stats.user_messages, 3);
+    assert_eq!(stats.system_messages, 2);
+    assert_eq!(stats.media_messages, 0);
+    // "This is a test message", "Hey I'm a test too!" and the two lines of the
+    // last message with the newline between them
+    assert_eq!(stats.total_characters, 22 + 19 + 35);
+    assert_eq!(stats.total_words, 5 + 5 + 6);
+    assert_eq!(stats.unique_authors, 3);
+    assert_eq!(
+        stats.date_range,
+        Some((Utc.with_ymd_and_hms(2017, 6, 3, 0, 45, 0).unwrap(), Utc.with_ymd_and_hms(2017, 9, 4, 1, 50, 0).unwrap()))
+    );
+    assert_eq!(stats.messages_per_author["TestBot"], 1);
+    assert_eq!(stats.average_message_length(), 76.0 / 3.0);
+    // Everyone sent one message, the first name wins
+    assert_eq!(stats.most_active_author(), Some("+410123456789"));
+
+    let empty = MessageStats::compute(&[]);
+    assert_eq!(empty, MessageStats::default());
+    assert_eq!(empty.average_message_length(), 0.0);
+    assert_eq!(empty.most_active_author(), None);
+}
+
+#[test]
+fn test_author_stats() {
+    let messages = parse_string(CHAT_EXAMPLE, None).unwrap();
+    let stats = compute_author_stats(&messages);
+
+    let total: usize = stats.values().map(|s| s.message_count).sum();
+    assert_eq!(total, MessageStats::compute(&messages).user_messages);
+    for author_stats in stats.values() {
+        assert!(author_stats.first_message <= author_stats.last_message, "{}", author_stats.name);
+    }
+    assert_eq!(stats["+410123456789"].word_count, 6);
+}
+
+#[test]
+fn test_parse_string_with_stats() {
+    let (messages, stats) = parse_string_with_stats(CHAT_EXAMPLE, None).unwrap();
+    assert_eq!(stats.total_lines, 6);
+    assert_eq!(stats.continuation_lines, 1);
+    assert_eq!(stats.discarded_lines, 0);
+    assert_eq!(stats.message_starts + stats.system_message_starts, messages.len());
+
+    // The BOM is stripped, but the title line before the first message is
+    // still not part of any message
+    let input = "\u{FEFF}WhatsApp Chat with Luke\n23/06/2018, 01:55 - Luke: Hey!";
+    let (messages, stats) = parse_string_with_stats(input, None).unwrap();
+    assert_eq!(stats.discarded_lines, 1);
+    assert_eq!(stats.message_starts + stats.system_message_starts, messages.len());
+}
+
+#[test]
+fn test_log_sink() {
+    use std::sync::{Arc, Mutex};
+
+    let log = Arc::new(Mutex::new(Vec::new()));
+    let sink_log = Arc::clone(&log);
+    let options = ParseStringOptions {
+        debug: true,
+        log_sink: Some(Box::new(move |message: &str| sink_log.lock().unwrap().push(message.to_string()))),
+        ..Default::default()
+    };
+    let messages = parse_string(CHAT_EXAMPLE, Some(options)).unwrap();
+
+    let log = log.lock().unwrap();
+    assert!(log.iter().any(|line| line.contains("Message aggregation complete")));
+    assert!(log.iter().any(|line| line.contains(&format!("Total messages found: {}", messages.len()))));
+    assert!(log.iter().any(|line| line.contains("Date format auto-detection result")));
+}
+
+#[test]
+fn test_locale_month_names() {
+    let options = ParseStringOptions {
+        locale_month_names: month_map_for_locale("de"),
+        ..Default::default()
+    };
+    let messages = parse_string("7. Januar 2023 14:30 - a: m\n8. März 2023 09:15 - Luke hat Leia hinzugefügt", Some(options)).unwrap();
+
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].date, Utc.with_ymd_and_hms(2023, 1, 7, 14, 30, 0).unwrap());
+    assert_eq!((messages[0].author.as_deref(), messages[0].message.as_str()), (Some("a"), "m"));
+    assert_eq!(messages[1].date, Utc.with_ymd_and_hms(2023, 3, 8, 9, 15, 0).unwrap());
+    assert!(messages[1].is_system());
+}
+
+#[test]
+fn test_locale_month_names_invalid_date() {
+    let options = || ParseStringOptions {
+        locale_month_names: month_map_for_locale("de"),
+        ..Default::default()
+    };
+    let input = "31. Februar 2023 14:30 - a: m\n7. Januar 2023 14:30 - a: m\n30. Februar 2023 14:31 - b: n\n8. März 2023 09:15 - b: o";
+
+    let (messages, warnings) = parse_string_verbose(input, options());
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].message, "m\n30. Februar 2023 14:31 - b: n");
+    assert!(matches!(&warnings[..], [ParseWarning::DroppedLine(err)] if err.line_number == 1));
+
+    let january = Utc.with_ymd_and_hms(2023, 1, 7, 14, 30, 0).unwrap();
+    let epochs = parse_epoch(input, Some(options())).unwrap();
+    assert_eq!(epochs.iter().map(|(timestamp, _, _)| *timestamp).collect::<Vec<_>>(), [january.timestamp(), Utc.with_ymd_and_hms(2023, 3, 8, 9, 15, 0).unwrap().timestamp()]);
+    let since = parse_since(input, Some(options()), january).unwrap();
+    assert_eq!((since.len(), since[0].message.as_str()), (1, "o"));
+}
+
 #[test]
 fn test_issue_237() {
     let messages = parse_string("30/12/2020 13:00 - a: m\n13/1/2021 13:00 - a: m", None).unwrap();
@@ -38,3 +159,607 @@ fn test_issue_237() {
         Utc.with_ymd_and_hms(2021, 1, 13, 13, 0, 0).unwrap()
     );
 }
+
+#[test]
+fn test_old_android_format() {
+    let input = "1/1/2020 9:05 - a: one\n\
+                 31/12/2020 23:59 - b: two\n\
+                 5/3/2020 9:5 - a: three\n\
+                 05/03/2020 09:05 - b: four\n\
+                 12/10/2020 0:00 - a: five";
+    let messages = parse_string(input, None).unwrap();
+    let dates: Vec<_> = messages.iter().map(|m| m.date).collect();
+
+    assert_eq!(
+        dates,
+        vec![
+            Utc.with_ymd_and_hms(2020, 1, 1, 9, 5, 0).unwrap(),
+            Utc.with_ymd_and_hms(2020, 12, 31, 23, 59, 0).unwrap(),
+            Utc.with_ymd_and_hms(2020, 3, 5, 9, 5, 0).unwrap(),
+            Utc.with_ymd_and_hms(2020, 3, 5, 9, 5, 0).unwrap(),
+            Utc.with_ymd_and_hms(2020, 10, 12, 0, 0, 0).unwrap(),
+        ]
+    );
+    assert_eq!(messages[4].message, "five");
+}
+
+#[test]
+fn test_mixed_12_and_24_hour_times() {
+    let input = "13/2/2020, 13:00 - a: 24-hour afternoon\n\
+                 13/2/2020, 1:00 PM - b: 12-hour afternoon\n\
+                 13/2/2020, 0:15 - a: 24-hour midnight\n\
+                 13/2/2020, 12:15 AM - b: 12-hour midnight\n\
+                 13/2/2020, 12:30 p.m. - a: 12-hour noon\n\
+                 13/2/2020, 9:45 a.m. - b: 12-hour morning\n\
+                 13/2/2020, 09:45 - a: 24-hour morning\n\
+                 13/2/2020, 11:59\u{202F}pm - b: 12-hour narrow space\n\
+                 13/2/2020, 23:59 - a: 24-hour night";
+    let messages = parse_string(input, None).unwrap();
+    let times: Vec<_> = messages.iter().map(|m| (m.date.hour(), m.date.minute())).collect();
+
+    assert_eq!(times, vec![(13, 0), (13, 0), (0, 15), (0, 15), (12, 30), (9, 45), (9, 45), (23, 59), (23, 59)]);
+    assert_eq!(messages[1].message, "12-hour afternoon");
+    assert_eq!(messages[7].author.as_deref(), Some("b"));
+}
+
+#[test]
+fn test_parse_epoch() {
+    let messages = parse_epoch(CHAT_EXAMPLE, None).unwrap();
+    assert_eq!(messages.len(), 5);
+    // The example is detected as month-first: June 3 2017, 00:45 UTC
+    assert_eq!(messages[2].0, 1496450700);
+    assert_eq!(messages[2].1, Some("Sample User".to_string()));
+    assert_eq!(messages[2].2, "This is a test message");
+
+    let parsed = parse_string(CHAT_EXAMPLE, None).unwrap();
+    for (epoch, message) in messages.iter().zip(parsed.iter()) {
+        assert_eq!(epoch.0, message.date.timestamp());
+    }
+}
+
+#[test]
+fn test_parse_string_size_limits() {
+    let too_many_bytes = parse_string(
+        CHAT_EXAMPLE,
+        Some(ParseStringOptions {
+            max_bytes: Some(100),
+            ..Default::default()
+        }),
+    );
+    assert!(matches!(too_many_bytes, Err(ParseError::TooLarge(SizeLimit::Bytes(100)))));
+
+    let too_many_lines = parse_string(
+        CHAT_EXAMPLE,
+        Some(ParseStringOptions {
+            max_lines: Some(3),
+            ..Default::default()
+        }),
+    );
+    assert!(matches!(too_many_lines, Err(ParseError::TooLarge(SizeLimit::Lines(3)))));
+
+    let within_limits = parse_string(
+        CHAT_EXAMPLE,
+        Some(ParseStringOptions {
+            max_bytes: Some(CHAT_EXAMPLE.len()),
+            max_lines: Some(6),
+            ..Default::default()
+        }),
+    );
+    assert_eq!(within_limits.unwrap().len(), 5);
+}
+
+// Runs on the memory-mapped path by default and on the fallback path with
+// `--no-default-features`
+#[test]
+fn test_parse_file_matches_parse_string() {
+    let path = std::env::temp_dir().join("wc_parser_test_parse_file.txt");
+    std::fs::write(&path, CHAT_EXAMPLE).unwrap();
+
+    let messages = parse_file(&path, None).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(messages, parse_string(CHAT_EXAMPLE, None).unwrap());
+}
+
+#[test]
+fn test_parse_file_size_limit() {
+    let path = std::env::temp_dir().join("wc_parser_test_parse_file_size_limit.txt");
+    std::fs::write(&path, CHAT_EXAMPLE).unwrap();
+
+    let err = parse_file(
+        &path,
+        Some(ParseStringOptions {
+            max_bytes: Some(100),
+            ..Default::default()
+        }),
+    )
+    .unwrap_err();
+    let inner = err.get_ref().and_then(|e| e.downcast_ref::<ParseError>());
+    assert!(matches!(inner, Some(ParseError::TooLarge(SizeLimit::Bytes(100)))));
+    assert_eq!(parse_file(&path, None).unwrap().len(), 5);
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_parse_file_size_limit_before_reading() {
+    // A sparse file far larger than the memory available to the tests, which
+    // would fail or take minutes if it were read before the size check.
+    let path = std::env::temp_dir().join("wc_parser_test_parse_file_size_limit_before_reading.txt");
+    std::fs::File::create(&path).unwrap().set_len(1 << 40).unwrap();
+    let options = || ParseStringOptions {
+        max_bytes: Some(100),
+        ..Default::default()
+    };
+
+    let err = parse_file(&path, Some(options())).unwrap_err();
+    let inner = err.get_ref().and_then(|e| e.downcast_ref::<ParseError>());
+    assert!(matches!(inner, Some(ParseError::TooLarge(SizeLimit::Bytes(100)))));
+    assert!(matches!(parse_files(&[&path], Some(options())), Err(ParseError::TooLarge(SizeLimit::Bytes(100)))));
+
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_parse_file_invalid_utf8() {
+    let path = std::env::temp_dir().join("wc_parser_test_parse_file_invalid_utf8.txt");
+    let mut contents = b"23/06/2018, 01:55 - Luke: Hey!".to_vec();
+    contents.push(0xFF);
+    std::fs::write(&path, &contents).unwrap();
+
+    let err = parse_file(&path, None).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(err.to_string().contains("from index 30"), "{}", err);
+}
+
+#[test]
+fn test_parse_string_count_matches_full_parse() {
+    assert_eq!(parse_string_count(CHAT_EXAMPLE), parse_string(CHAT_EXAMPLE, None).unwrap().len());
+    assert_eq!(parse_string_count(""), 0);
+}
+
+#[test]
+fn test_parse_file_count() {
+    let path = std::env::temp_dir().join("wc_parser_test_parse_file_count.txt");
+    std::fs::write(&path, CHAT_EXAMPLE).unwrap();
+    assert_eq!(parse_file_count(&path).unwrap(), 5);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_parse_since() {
+    let all = parse_string(CHAT_EXAMPLE, None).unwrap();
+    let since = all[2].date;
+    let recent = parse_since(CHAT_EXAMPLE, None, since).unwrap();
+
+    assert_eq!(recent.len(), 2);
+    assert_eq!(recent[0], all[3]);
+    assert_eq!(recent[1], all[4]);
+    assert!(recent.iter().all(|m| m.date > since));
+}
+
+#[test]
+fn test_parse_string_authors() {
+    let mut expected: Vec<String> = parse_string(CHAT_EXAMPLE, None)
+        .unwrap()
+        .into_iter()
+        .filter_map(|m| m.author)
+        .collect();
+    expected.sort();
+    expected.dedup();
+
+    assert_eq!(parse_string_authors(CHAT_EXAMPLE), expected);
+    assert_eq!(parse_string_authors(CHAT_EXAMPLE), vec!["+410123456789", "Sample User", "TestBot"]);
+}
+
+#[test]
+fn test_parse_file_authors() {
+    let path = std::env::temp_dir().join("wc_parser_test_parse_file_authors.txt");
+    std::fs::write(&path, CHAT_EXAMPLE).unwrap();
+    assert_eq!(parse_file_authors(&path).unwrap(), parse_string_authors(CHAT_EXAMPLE));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_format_messages_round_trip() {
+    let messages = parse_string(CHAT_EXAMPLE, Some(ParseStringOptions { days_first: Some(false), ..Default::default() })).unwrap();
+    let exported = format_messages(&messages, &MessageFormat::default());
+    let reparsed = parse_string(&exported, Some(ParseStringOptions { days_first: Some(true), ..Default::default() })).unwrap();
+
+    assert_eq!(reparsed, messages);
+}
+
+#[test]
+fn test_parse_string_date_range() {
+    let messages = parse_string(CHAT_EXAMPLE, None).unwrap();
+    let expected = (
+        messages.iter().map(|m| m.date).min().unwrap(),
+        messages.iter().map(|m| m.date).max().unwrap(),
+    );
+
+    assert_eq!(parse_string_date_range(CHAT_EXAMPLE, None), Some(expected));
+    assert_eq!(parse_string_date_range("", None), None);
+
+    let path = std::env::temp_dir().join("wc_parser_test_parse_file_date_range.txt");
+    std::fs::write(&path, CHAT_EXAMPLE).unwrap();
+    assert_eq!(parse_file_date_range(&path, None).unwrap(), Some(expected));
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_parse_string_crlf() {
+    let crlf = CHAT_EXAMPLE.replace('\n', "\r\n");
+    let expected = parse_string(CHAT_EXAMPLE, None).unwrap();
+    let messages = parse_string(&crlf, None).unwrap();
+
+    assert_eq!(messages.len(), expected.len());
+    for (message, expected) in messages.iter().zip(expected.iter()) {
+        assert_eq!(message.author, expected.author);
+        assert_eq!(message.message, expected.message);
+        assert_eq!(message.date, expected.date);
+    }
+    assert_eq!(parse_string_count(&crlf), 5);
+    assert_eq!(parse_string_authors(&crlf), parse_string_authors(CHAT_EXAMPLE));
+}
+
+#[test]
+fn test_parse_string_bom() {
+    let with_bom = format!("\u{FEFF}{}", CHAT_EXAMPLE);
+    let messages = parse_string(&with_bom, None).unwrap();
+
+    assert_eq!(messages.len(), 5);
+    assert_eq!(messages, parse_string(CHAT_EXAMPLE, None).unwrap());
+    assert_eq!(parse_string_count(&with_bom), 5);
+
+    let path = std::env::temp_dir().join("wc_parser_test_parse_file_bom.txt");
+    std::fs::write(&path, &with_bom).unwrap();
+    assert_eq!(parse_file(&path, None).unwrap().len(), 5);
+    std::fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn test_parse_string_lenient() {
+    let input = "Exported by a buggy tool\n\u{1}\u{2}\u{3}\n06/03/2017, 00:45 - a: one\n06/03/2017, 00:46 - b: two\n06/03/2017, 00:47 - a: three";
+    let (messages, errors) = parse_string_lenient(input, ParseStringOptions::default());
+
+    assert_eq!((messages.len(), errors.len()), (3, 2));
+    assert_eq!(errors[0].line_number, 1);
+    assert_eq!(errors[0].raw_line, "Exported by a buggy tool");
+    assert_eq!(errors[1].line_number, 2);
+    assert_eq!(errors[1].reason, ParseLineErrorReason::NoPrecedingMessage);
+    assert_eq!(errors[1].to_string(), "line 2: line is not part of any message");
+
+    let (messages, errors) = parse_string_lenient(CHAT_EXAMPLE, ParseStringOptions::default());
+    assert_eq!((messages.len(), errors.len()), (5, 0));
+}
+
+#[test]
+fn test_parse_string_verbose() {
+    let input = "Exported by a buggy tool\n01/02/2017, 00:45 - a: one\n01/02/2017, 00:46 - b: two";
+    let (messages, warnings) = parse_string_verbose(input, ParseStringOptions::default());
+
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[1].message, "two");
+    assert_eq!(warnings.len(), 2);
+    assert!(matches!(&warnings[0], ParseWarning::DroppedLine(err) if err.line_number == 1));
+    assert_eq!(warnings[1], ParseWarning::DefaultedDateOrder);
+
+    let (messages, warnings) = parse_string_verbose(CHAT_EXAMPLE, ParseStringOptions::default());
+    assert_eq!((messages.len(), warnings.len()), (5, 0));
+}
+
+#[test]
+fn test_parse_bytes() {
+    assert_eq!(
+        parse_bytes(CHAT_EXAMPLE.as_bytes(), None).unwrap(),
+        parse_string(CHAT_EXAMPLE, None).unwrap()
+    );
+    assert!(parse_bytes(b"", None).unwrap().is_empty());
+
+    let mut invalid = b"06/03/2017, 00:45 - a: one".to_vec();
+    invalid.push(0xFF);
+    match parse_bytes(&invalid, None) {
+        Err(ParseError::Utf8Error(err)) => assert_eq!(err.valid_up_to(), invalid.len() - 1),
+        other => panic!("expected a UTF-8 error, got {:?}", other),
+    }
+}
+
+#[test]
+fn test_parse_chat_title() {
+    let chat = parse_chat(CHAT_EXAMPLE, None).unwrap();
+    assert_eq!(chat.title.as_deref(), Some("ShortChat"));
+    assert_eq!(chat.messages, parse_string(CHAT_EXAMPLE, None).unwrap());
+
+    let chat = parse_chat("06/03/2017, 00:45 - a: one", None).unwrap();
+    assert_eq!(chat.title, None);
+}
+
+#[test]
+fn test_message_display_round_trip() {
+    let input = "23/06/2021, 13:05 - You created group \"Test\"\n23/06/2021, 13:06 - Luke: one\ntwo";
+    for message in parse_string(input, None).unwrap() {
+        let formatted = format!("{}", message);
+        assert!(!formatted.is_empty());
+
+        let reparsed = parse_string(&formatted, None).unwrap();
+        assert_eq!(reparsed.len(), 1);
+        assert_eq!(reparsed[0].date, message.date);
+        assert_eq!(reparsed[0].author, message.author);
+        assert_eq!(reparsed[0].message, message.message);
+    }
+}
+
+#[test]
+fn test_messages_to_markdown() {
+    let markdown = messages_to_markdown(&parse_string(CHAT_EXAMPLE, None).unwrap());
+
+    assert_eq!(markdown.matches("## ").count(), 3);
+    assert!(markdown.starts_with("## 2017-06-03\n\n*Messages to this group are now secured with end-to-end encryption. Tap for more info.*\n"));
+    assert!(markdown.contains("\n*You created group \"ShortChat\"*\n"));
+    assert!(markdown.contains("\n**Sample User** (00:45): This is a test message\n"));
+    assert!(markdown.contains("\n## 2017-08-05\n\n**TestBot** (01:48): Hey I'm a test too!\n"));
+    assert!(markdown.ends_with("**+410123456789** (01:50): How are you?\n  Is everything alright?\n"));
+}
+
+#[test]
+fn test_parse_string_system_kind() {
+    let messages = parse_string(CHAT_EXAMPLE, None).unwrap();
+
+    assert_eq!(messages[0].system_kind, Some(SystemMessageKind::EndToEndEncryption));
+    assert_eq!(messages[1].system_kind, Some(SystemMessageKind::GroupCreated { name: "ShortChat".to_string() }));
+    assert!(messages[2..].iter().all(|m| m.system_kind.is_none()));
+}
+
+#[test]
+fn test_parse_metadata() {
+    let messages = parse_string(CHAT_EXAMPLE, None).unwrap();
+    let metadata = parse_metadata(&messages);
+
+    assert!(metadata.is_group_chat);
+    assert_eq!(metadata.group_name.as_deref(), Some("ShortChat"));
+    assert_eq!(metadata.participant_count, Some(3));
+    assert_eq!(metadata.export_start, Some(messages[0].date));
+}
+
+#[test]
+fn test_load_and_merge() {
+    let dir = std::env::temp_dir();
+    let first = dir.join("wc_parser_merge_first.txt");
+    let second = dir.join("wc_parser_merge_second.txt");
+    let missing = dir.join("wc_parser_merge_missing.txt");
+    std::fs::write(&first, "13/06/2017, 00:45 - a: one\n13/06/2017, 00:46 - b: two\n13/06/2017, 00:47 - a: three").unwrap();
+    std::fs::write(&second, "13/06/2017, 00:46 - b: two\n13/06/2017, 00:47 - a: three\n14/06/2017, 09:00 - b: four").unwrap();
+
+    let (messages, errors) = load_and_merge(&[second.clone(), first.clone(), missing.clone()], None);
+    std::fs::remove_file(&first).unwrap();
+    std::fs::remove_file(&second).unwrap();
+
+    let bodies: Vec<&str> = messages.iter().map(|m| m.message.as_str()).collect();
+    assert_eq!(bodies, vec!["one", "two", "three", "four"]);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].0, missing);
+    assert!(matches!(errors[0].1, ParseError::Io(_)));
+}
+
+#[test]
+fn test_load_and_merge_same_minute() {
+    let dir = std::env::temp_dir();
+    let first = dir.join("wc_parser_merge_same_minute_first.txt");
+    let second = dir.join("wc_parser_merge_same_minute_second.txt");
+    std::fs::write(&first, "13/06/2017, 00:45 - Zed: did you see it?\n13/06/2017, 00:45 - Amy: yes\n13/06/2017, 00:45 - Zed: cool").unwrap();
+    std::fs::write(&second, "13/06/2017, 00:45 - Amy: yes\n13/06/2017, 00:45 - Zed: cool\n13/06/2017, 00:45 - Amy: bye").unwrap();
+
+    let (messages, errors) = load_and_merge(&[first.clone(), second.clone()], None);
+    std::fs::remove_file(&first).unwrap();
+    std::fs::remove_file(&second).unwrap();
+
+    let bodies: Vec<&str> = messages.iter().map(|m| m.message.as_str()).collect();
+    assert_eq!(bodies, vec!["did you see it?", "yes", "cool", "bye"]);
+    assert!(errors.is_empty());
+}
+
+#[test]
+fn test_parse_files() {
+    let dir = std::env::temp_dir();
+    let full = dir.join("wc_parser_parse_files_full.txt");
+    let subset = dir.join("wc_parser_parse_files_subset.txt");
+    let missing = dir.join("wc_parser_parse_files_missing.txt");
+    std::fs::write(&full, "13/06/2017, 00:45 - a: one\n13/06/2017, 00:46 - b: two\n13/06/2017, 00:47 - a: three\n14/06/2017, 09:00 - b: four").unwrap();
+    std::fs::write(&subset, "13/06/2017, 00:46 - b: two\n13/06/2017, 00:47 - a: three").unwrap();
+
+    let messages = parse_files(&[&subset, &full], None).unwrap();
+    let err = parse_files(&[&full, &missing], None).unwrap_err();
+    let (merged, _) = load_and_merge(&[subset.clone(), full.clone()], None);
+    std::fs::remove_file(&full).unwrap();
+    std::fs::remove_file(&subset).unwrap();
+
+    let bodies: Vec<&str> = messages.iter().map(|m| m.message.as_str()).collect();
+    assert_eq!(bodies, vec!["one", "two", "three", "four"]);
+    assert!(messages.windows(2).all(|w| w[0].date <= w[1].date));
+    assert_eq!(messages, merged);
+    assert!(matches!(err, ParseError::Io(_)));
+}
+
+#[cfg(feature = "encoding")]
+#[test]
+fn test_parse_file_with_encoding() {
+    let path = std::env::temp_dir().join("wc_parser_latin1.txt");
+    std::fs::write(&path, b"13/06/2017, 00:45 - Ren\xE9: caf\xE9 \x80").unwrap();
+    let messages = wc_parser::parse_file_with_encoding(&path, None).unwrap();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(messages.len(), 1);
+    assert_eq!(messages[0].author.as_deref(), Some("René"));
+    assert_eq!(messages[0].message, "café €");
+}
+
+#[test]
+fn test_parse_string_progress_callback() {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    let input: String = (0..2500).map(|i| format!("13/06/2017, 00:45 - a: message {}\n", i)).collect();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let last = Arc::new(Mutex::new(None));
+    let options = ParseStringOptions {
+        progress_callback: Some(Box::new({
+            let calls = Arc::clone(&calls);
+            let last = Arc::clone(&last);
+            move |processed, total| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                *last.lock().unwrap() = Some((processed, total));
+            }
+        })),
+        ..Default::default()
+    };
+
+    let messages = parse_string(&input, Some(options)).unwrap();
+
+    // Every 1000 lines, then once at the end (2500 lines + the empty last one)
+    assert_eq!(calls.load(Ordering::SeqCst), 3);
+    assert_eq!(*last.lock().unwrap(), Some((2501, 2501)));
+    assert_eq!(messages, parse_string(&input, None).unwrap());
+}
+
+/// Parses headers like `<2021.06.23 13:05> `.
+struct AngleBracketParser;
+
+impl wc_parser::datetime::DateTimeParser for AngleBracketParser {
+    fn parse(&self, header: &str) -> Option<(chrono::NaiveDateTime, usize)> {
+        let end = header.strip_prefix('<')?.find('>')? + 1;
+        let date = chrono::NaiveDateTime::parse_from_str(&header[1..end], "%Y.%m.%d %H:%M").ok()?;
+        Some((date, end + 2))
+    }
+}
+
+fn angle_bracket_options() -> ParseStringOptions {
+    ParseStringOptions {
+        date_time_parser: Some(Box::new(AngleBracketParser)),
+        ..Default::default()
+    }
+}
+
+#[test]
+fn test_parse_string_custom_date_time_parser() {
+    let input = "<2021.06.23 13:05> Luke: Hey!\nHow are you?\n<2021.06.23 13:07> Leia left\n<2021.06.24 09:00> Luke: Bye";
+    let messages = parse_string(input, Some(angle_bracket_options())).unwrap();
+
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[0].date, Utc.with_ymd_and_hms(2021, 6, 23, 13, 5, 0).unwrap());
+    assert_eq!(messages[0].author.as_deref(), Some("Luke"));
+    assert_eq!(messages[0].message, "Hey!\nHow are you?");
+    assert_eq!(messages[1].system_kind, Some(SystemMessageKind::ParticipantLeft));
+    assert_eq!(messages[2].date, Utc.with_ymd_and_hms(2021, 6, 24, 9, 0, 0).unwrap());
+    assert_eq!(messages[2].message, "Bye");
+}
+
+#[test]
+fn test_custom_date_time_parser_entry_points() {
+    let input = "orphan line\n<2021.06.23 13:05> Luke: Hey!\nHow are you?\n<2021.06.23 13:07> Leia left\n<2021.06.24 09:00> Luke: Bye";
+    let expected = parse_string(input, Some(angle_bracket_options())).unwrap();
+    let first = Utc.with_ymd_and_hms(2021, 6, 23, 13, 5, 0).unwrap();
+    let last = Utc.with_ymd_and_hms(2021, 6, 24, 9, 0, 0).unwrap();
+
+    let (messages, errors) = parse_string_lenient(input, angle_bracket_options());
+    assert_eq!(messages, expected);
+    assert_eq!(errors.len(), 1);
+    assert_eq!(errors[0].line_number, 1);
+
+    let (messages, warnings) = parse_string_verbose(input, angle_bracket_options());
+    assert_eq!(messages, expected);
+    assert_eq!(warnings.len(), 1);
+    assert!(matches!(warnings[0], ParseWarning::DroppedLine(_)));
+
+    let (messages, stats) = parse_string_with_stats(input, Some(angle_bracket_options())).unwrap();
+    assert_eq!(messages, expected);
+    assert_eq!((stats.message_starts, stats.system_message_starts, stats.continuation_lines, stats.discarded_lines), (2, 1, 1, 1));
+
+    let epochs = parse_epoch(input, Some(angle_bracket_options())).unwrap();
+    assert_eq!(epochs[0], (first.timestamp(), Some("Luke".to_string()), "Hey!\nHow are you?".to_string()));
+    assert_eq!(parse_since(input, Some(angle_bracket_options()), first).unwrap(), expected[1..]);
+    assert_eq!(parse_string_date_range(input, Some(angle_bracket_options())), Some((first, last)));
+
+    let progress = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+    let reported = progress.clone();
+    let options = ParseStringOptions {
+        progress_callback: Some(Box::new(move |done, total| reported.lock().unwrap().push((done, total)))),
+        progress_report_interval: 2,
+        ..angle_bracket_options()
+    };
+    assert_eq!(parse_string(input, Some(options)).unwrap(), expected);
+    assert_eq!(*progress.lock().unwrap(), [(2, 5), (4, 5), (5, 5)]);
+}
+
+#[test]
+fn test_is_message_start() {
+    let lines: Vec<&str> = CHAT_EXAMPLE.lines().collect();
+    let user_starts: Vec<bool> = lines.iter().map(|line| is_message_start(line)).collect();
+    let any_starts: Vec<bool> = lines.iter().map(|line| is_system_message_start(line)).collect();
+
+    assert_eq!(user_starts, [false, false, true, true, true, false]);
+    assert_eq!(any_starts, [true, true, true, true, true, false]);
+
+    // Same classification as the parser: a message per start, system messages
+    // for the starts without an author
+    let messages = parse_string(CHAT_EXAMPLE, None).unwrap();
+    assert_eq!(messages.len(), any_starts.iter().filter(|&&start| start).count());
+    let system: Vec<bool> = messages.iter().map(|m| m.is_system()).collect();
+    let expected: Vec<bool> = user_starts.iter().zip(&any_starts).filter(|(_, any)| **any).map(|(user, _)| !user).collect();
+    assert_eq!(system, expected);
+}
+
+#[test]
+fn test_parse_string_custom_regexes() {
+    use regex::Regex;
+
+    let input = "[2021-06-23|13:05] Luke > Hey!\nHow are you?\n[2021-06-23|13:07] Leia joined\n[2021-06-24|09:00 PM] Luke > Bye";
+    let options = ParseStringOptions {
+        custom_message_regex: Some(Regex::new(r"(?s)^\[(\d{4}-\d{2}-\d{2})\|(\d{2}:\d{2})(?: ([AP]M))?\] (.+?) > (.*)").unwrap()),
+        custom_system_regex: Some(Regex::new(r"(?s)^\[(\d{4}-\d{2}-\d{2})\|(\d{2}:\d{2})(?: ([AP]M))?\] (.*)").unwrap()),
+        ..Default::default()
+    };
+    let messages = parse_string(input, Some(options)).unwrap();
+
+    assert_eq!(messages.len(), 3);
+    assert_eq!(messages[0].date, Utc.with_ymd_and_hms(2021, 6, 23, 13, 5, 0).unwrap());
+    assert_eq!(messages[0].author.as_deref(), Some("Luke"));
+    assert_eq!(messages[0].message, "Hey!\nHow are you?");
+    assert_eq!(messages[1].author, None);
+    assert_eq!(messages[1].message, "Leia joined");
+    assert_eq!(messages[2].date, Utc.with_ymd_and_hms(2021, 6, 24, 21, 0, 0).unwrap());
+    assert_eq!(messages[2].message, "Bye");
+
+    // The built-in regexes don't recognize the format
+    assert!(parse_string(input, None).unwrap().is_empty());
+}
+
+#[test]
+fn test_parse_string_custom_regex_incomplete_date_time() {
+    use regex::Regex;
+
+    let input = "[2021-06|13:05] Luke > Hey!\n[2021-06-23|13] Leia > Hi!\n[2021-06-24|09:00] Luke > Bye";
+    let options = ParseStringOptions {
+        custom_message_regex: Some(Regex::new(r"(?s)^\[([\d-]+)\|([\d:]+)(?: ([AP]M))?\] (.+?) > (.*)").unwrap()),
+        ..Default::default()
+    };
+    let messages = parse_string(input, Some(options)).unwrap();
+
+    assert_eq!(messages.len(), 3);
+    let epoch = Utc.with_ymd_and_hms(1970, 1, 1, 0, 0, 0).unwrap();
+    assert_eq!((messages[0].date, messages[0].author.as_deref()), (epoch, Some("Luke")));
+    assert_eq!((messages[1].date, messages[1].author.as_deref()), (epoch, Some("Leia")));
+    assert_eq!(messages[2].date, Utc.with_ymd_and_hms(2021, 6, 24, 9, 0, 0).unwrap());
+}
+
+#[test]
+fn test_parse_string_verbose_invalid_date_line() {
+    let input = "23/06/2018, 01:55 - Luke: one\ntwo\n30/02/2018, 01:56 - Luke: three";
+    let (messages, warnings) = parse_string_verbose(input, ParseStringOptions::default());
+
+    assert_eq!(messages.len(), 2);
+    assert_eq!(warnings.len(), 1);
+    assert!(matches!(&warnings[0], ParseWarning::InvalidDate(err) if err.line_number == 3 && err.reason == ParseLineErrorReason::InvalidDate));
+    assert_eq!(warnings[0].to_string(), "line 3: invalid date, used 1970-01-01 instead");
+}
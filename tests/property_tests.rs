@@ -0,0 +1,181 @@
+//! Properties of the date and time helpers, checked against every input in
+//! their valid ranges rather than a handful of examples.
+
+use regex::Regex;
+use wc_parser::datetime::{convert_time_12_to_24, normalize_ampm, normalize_date, normalize_digits, normalize_time};
+
+/// The ways a number can be written in an export: bare or zero padded to two
+/// digits.
+fn spellings(n: u32) -> Vec<String> {
+    let mut out = vec![n.to_string()];
+    if n < 10 {
+        out.push(format!("{:02}", n));
+    }
+    out
+}
+
+/// Every year spelling: two digit years (`0` to `99`) and four digit ones.
+fn years() -> impl Iterator<Item = String> {
+    (0..100).flat_map(spellings).chain((1900..2100).map(|y| y.to_string()))
+}
+
+/// Every `h:mm` / `h:mm:ss` time with hours in `hours`, using both `:` and `.`
+/// as separators. Minutes and seconds are sampled every 7 to keep it quick.
+fn times(hours: std::ops::RangeInclusive<u32>) -> Vec<String> {
+    let mut out = Vec::new();
+    for h in hours {
+        for hour in spellings(h) {
+            for m in (0..60).step_by(7) {
+                for sep in [":", "."] {
+                    out.push(format!("{}{}{:02}", hour, sep, m));
+                    out.push(format!("{}{}{:02}{}{:02}", hour, sep, m, sep, (m * 3) % 60));
+                }
+            }
+        }
+    }
+    out
+}
+
+/// All the spellings of AM and PM found in exports.
+const AMPM: [&str; 12] = ["AM", "am", "a.m.", "A.M.", "a. m.", "a.m", "PM", "pm", "p.m.", "P.M.", "p. m.", "p.m"];
+
+fn hours_of(time: &str) -> u32 {
+    time.split(':').next().unwrap().parse().unwrap()
+}
+
+#[test]
+fn normalize_date_year_has_4_digits() {
+    for year in years() {
+        let (y, _, _) = normalize_date(&year, "1", "1").unwrap();
+        assert_eq!(y.len(), 4, "year {:?}", year);
+    }
+}
+
+#[test]
+fn normalize_date_month_has_2_digits() {
+    for month in (1..=12).flat_map(spellings) {
+        let (_, m, _) = normalize_date("2020", &month, "1").unwrap();
+        assert_eq!(m.len(), 2, "month {:?}", month);
+    }
+}
+
+#[test]
+fn normalize_date_day_has_2_digits() {
+    for day in (1..=31).flat_map(spellings) {
+        let (_, _, d) = normalize_date("2020", "1", &day).unwrap();
+        assert_eq!(d.len(), 2, "day {:?}", day);
+    }
+}
+
+#[test]
+fn normalize_date_keeps_values() {
+    for year in years() {
+        for day in (1..=31).flat_map(spellings) {
+            let (y, m, d) = normalize_date(&year, &day, &day).unwrap();
+            let expected_year: u32 = year.parse().unwrap();
+            let expected_year = match expected_year {
+                _ if year.len() > 2 => expected_year,
+                0..70 => 2000 + expected_year,
+                _ => 1900 + expected_year,
+            };
+            assert_eq!(y.parse::<u32>().unwrap(), expected_year);
+            assert_eq!(m.parse::<u32>().unwrap(), day.parse::<u32>().unwrap());
+            assert_eq!(d, m);
+        }
+    }
+}
+
+#[test]
+fn normalize_date_is_idempotent() {
+    for year in years() {
+        for day in (1..=31).flat_map(spellings) {
+            let once = normalize_date(&year, &day, &day).unwrap();
+            assert_eq!(normalize_date(&once.0, &once.1, &once.2).unwrap(), once);
+        }
+    }
+}
+
+#[test]
+fn convert_time_am_hours_are_0_to_11() {
+    for time in times(1..=12) {
+        let converted = convert_time_12_to_24(&time, "AM").unwrap();
+        assert!(hours_of(&converted) <= 11, "{:?} became {:?}", time, converted);
+    }
+}
+
+#[test]
+fn convert_time_pm_hours_are_12_to_23() {
+    for time in times(1..=12) {
+        let converted = convert_time_12_to_24(&time, "PM").unwrap();
+        assert!((12..=23).contains(&hours_of(&converted)), "{:?} became {:?}", time, converted);
+    }
+}
+
+#[test]
+fn convert_time_keeps_minutes_and_seconds() {
+    for time in times(1..=12) {
+        for ampm in ["AM", "PM"] {
+            let converted = convert_time_12_to_24(&time, ampm).unwrap();
+            let rest: Vec<&str> = time.split([':', '.']).skip(1).collect();
+            assert_eq!(converted.split(':').skip(1).collect::<Vec<_>>(), rest);
+        }
+    }
+}
+
+#[test]
+fn convert_time_keeps_24_hour_times() {
+    for time in times(13..=23) {
+        for ampm in ["AM", "PM"] {
+            let converted = convert_time_12_to_24(&time, ampm).unwrap();
+            assert_eq!(hours_of(&converted), hours_of(&time.replace('.', ":")));
+        }
+    }
+}
+
+#[test]
+fn normalize_ampm_is_idempotent() {
+    for ampm in AMPM {
+        let once = normalize_ampm(ampm);
+        assert_eq!(normalize_ampm(&once), once);
+    }
+}
+
+#[test]
+fn normalize_ampm_gives_am_or_pm() {
+    for ampm in AMPM {
+        let expected = if ampm.to_lowercase().starts_with('a') { "AM" } else { "PM" };
+        assert_eq!(normalize_ampm(ampm), expected);
+    }
+}
+
+#[test]
+fn normalize_time_has_hh_mm_ss_format() {
+    let re = Regex::new(r"^\d{2}:\d{2}:\d{2}$").unwrap();
+    for time in times(0..=23) {
+        let normalized = normalize_time(&time);
+        assert!(re.is_match(&normalized), "{:?} became {:?}", time, normalized);
+    }
+}
+
+#[test]
+fn normalize_time_is_idempotent() {
+    for time in times(0..=23) {
+        let once = normalize_time(&time);
+        assert_eq!(normalize_time(&once), once);
+    }
+}
+
+#[test]
+fn normalize_digits_matches_ascii_numbers() {
+    // Arabic-Indic, Devanagari and fullwidth digits
+    for zero in ['\u{0660}', '\u{0966}', '\u{FF10}'] {
+        for n in 0..=2100u32 {
+            let written: String = n
+                .to_string()
+                .chars()
+                .map(|c| char::from_u32(zero as u32 + c.to_digit(10).unwrap()).unwrap())
+                .collect();
+            assert_eq!(normalize_digits(&written), n.to_string());
+        }
+    }
+}